@@ -497,11 +497,19 @@ fn import_tflite_types() {
         .opaque_type("tflite::InterpreterBuilder")
         .allowlist_type("tflite::Interpreter")
         .opaque_type("tflite::Interpreter")
+        .allowlist_type("tflite::Subgraph")
+        .opaque_type("tflite::Subgraph")
+        .allowlist_type("tflite::SignatureRunner")
+        .opaque_type("tflite::SignatureRunner")
         .allowlist_type("tflite::ops::builtin::BuiltinOpResolver")
         .opaque_type("tflite::ops::builtin::BuiltinOpResolver")
         .allowlist_type("tflite::OpResolver")
         .opaque_type("tflite::OpResolver")
         .allowlist_type("TfLiteTensor")
+        .allowlist_type("TfLiteAffineQuantization")
+        .allowlist_type("TfLiteContext")
+        .allowlist_type("TfLiteNode")
+        .allowlist_type("TfLiteRegistration")
         .opaque_type("std::string")
         .opaque_type("std::basic_string.*")
         .opaque_type("std::map.*")
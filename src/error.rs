@@ -6,6 +6,34 @@ use thiserror::Error;
 pub enum Error {
     #[error(transparent)]
     IoError(#[from] IoError),
+    /// The model could not be parsed, failed verification or has an invalid graph.
+    #[error("model verification failed: {0}")]
+    ModelVerification(String),
+    /// The model uses an operator, or operator version, the op resolver does not provide.
+    #[error("unsupported op: {0}")]
+    UnsupportedOp(String),
+    /// Tensors could not be (re)allocated, or were accessed before being allocated.
+    #[error("allocation failed: {0}")]
+    Allocation(String),
+    /// An operator failed while running the graph.
+    #[error("invoke failed: {0}")]
+    Invoke(String),
+    /// A tensor was accessed as a type other than its element type.
+    #[error("type mismatch: {0}")]
+    TypeMismatch(String),
+    /// A tensor, signature or node index or name does not exist.
+    #[error("invalid index: {0}")]
+    InvalidIndex(String),
+    /// An `InterpreterState` is corrupt or does not match the variable tensors of the
+    /// interpreter it is restored into.
+    #[error("invalid interpreter state: {0}")]
+    InvalidState(String),
+    /// A delegate could not be created or applied to the graph.
+    #[error("delegate failed: {0}")]
+    Delegate(String),
+    /// `invoke` was aborted through a `CancellationToken` or its timeout.
+    #[error("invoke was cancelled")]
+    Cancelled,
     #[error("`{0}`")]
     InternalError(String),
 }
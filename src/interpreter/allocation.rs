@@ -0,0 +1,81 @@
+use std::alloc::{self, Layout};
+use std::ops::{Deref, DerefMut};
+use std::ptr::NonNull;
+use std::slice;
+
+/// Alignment TensorFlow Lite requires for custom tensor allocations (`kDefaultTensorAlignment`).
+pub const TENSOR_ALIGNMENT: usize = 64;
+
+/// A zero-initialized heap buffer aligned to `TENSOR_ALIGNMENT`, suitable as the backing store
+/// of a tensor in `Interpreter::set_custom_allocation`.
+pub struct AlignedBuffer {
+    ptr: NonNull<u8>,
+    len: usize,
+}
+
+unsafe impl Send for AlignedBuffer {}
+unsafe impl Sync for AlignedBuffer {}
+
+impl AlignedBuffer {
+    pub fn new(len: usize) -> Self {
+        if len == 0 {
+            return Self { ptr: Self::dangling(), len };
+        }
+        let layout = Self::layout(len);
+        let ptr = unsafe { alloc::alloc_zeroed(layout) };
+        let ptr = NonNull::new(ptr).unwrap_or_else(|| alloc::handle_alloc_error(layout));
+        Self { ptr, len }
+    }
+
+    fn layout(len: usize) -> Layout {
+        Layout::from_size_align(len, TENSOR_ALIGNMENT).expect("invalid buffer size")
+    }
+
+    fn dangling() -> NonNull<u8> {
+        NonNull::<Alignment>::dangling().cast()
+    }
+}
+
+/// A type aligned to `TENSOR_ALIGNMENT`, whose dangling pointer backs empty buffers.
+#[repr(align(64))]
+struct Alignment;
+
+const _: () = assert!(std::mem::align_of::<Alignment>() == TENSOR_ALIGNMENT);
+
+impl Drop for AlignedBuffer {
+    fn drop(&mut self) {
+        if self.len != 0 {
+            unsafe { alloc::dealloc(self.ptr.as_ptr(), Self::layout(self.len)) };
+        }
+    }
+}
+
+impl Deref for AlignedBuffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl DerefMut for AlignedBuffer {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe { slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aligned_buffer() {
+        for len in [0, 1, 63, 64, 1000] {
+            let mut buffer = AlignedBuffer::new(len);
+            assert_eq!(buffer.len(), len);
+            assert_eq!(buffer.as_ptr() as usize % TENSOR_ALIGNMENT, 0);
+            assert!(buffer.iter().all(|&b| b == 0));
+            buffer.fill(7);
+        }
+    }
+}
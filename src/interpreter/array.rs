@@ -0,0 +1,98 @@
+use ndarray::{ArrayBase, ArrayD, ArrayViewD, ArrayViewMutD, Data, Dimension, IxDyn, ShapeError};
+
+use super::context::ElemKindOf;
+use super::op_resolver::OpResolver;
+use super::{InputTensor, Interpreter, OutputTensor, OwnedTensor, TensorValue};
+use crate::{Error, Result};
+
+impl<Op> Interpreter<'_, Op>
+where
+    Op: OpResolver,
+{
+    /// Returns a view of the `index`th input with the dimensions of the tensor,
+    /// see `Interpreter::input`.
+    pub fn input_array_mut<T>(&mut self, index: usize) -> Result<ArrayViewMutD<'_, T>>
+    where
+        T: ElemKindOf,
+    {
+        self.input(index)?.into_array_mut()
+    }
+
+    /// Returns a view of the `index`th output with the dimensions of the tensor,
+    /// see `Interpreter::output`.
+    pub fn output_array<T>(&self, index: usize) -> Result<ArrayViewD<'_, T>>
+    where
+        T: ElemKindOf,
+    {
+        self.output(index)?.as_array()
+    }
+}
+
+impl<'i, T> InputTensor<'i, T> {
+    pub fn into_array_mut(self) -> Result<ArrayViewMutD<'i, T>> {
+        let (info, data) = self.into_parts();
+        ArrayViewMutD::from_shape(IxDyn(&info.dims), data).map_err(shape_error)
+    }
+}
+
+impl<'i, T> OutputTensor<'i, T> {
+    pub fn as_array(&self) -> Result<ArrayViewD<'i, T>> {
+        ArrayViewD::from_shape(IxDyn(self.shape()), self.as_slice()).map_err(shape_error)
+    }
+}
+
+impl OwnedTensor {
+    /// Copies the elements, which must be of type `T`, into an array of the
+    /// dimensions of the tensor.
+    pub fn to_array<T>(&self) -> Result<ArrayD<T>>
+    where
+        T: ElemKindOf + Copy,
+    {
+        ArrayD::from_shape_vec(IxDyn(&self.info.dims), self.values()?).map_err(shape_error)
+    }
+}
+
+impl TensorValue {
+    /// Creates a value with the shape and elements of `array`, in logical order.
+    pub fn from_array<T, S, D>(array: &ArrayBase<S, D>) -> Result<Self>
+    where
+        T: ElemKindOf + Copy,
+        S: Data<Elem = T>,
+        D: Dimension,
+    {
+        let values: Vec<T> = array.iter().copied().collect();
+        Self::new(array.shape(), &values)
+    }
+}
+
+fn shape_error(e: ShapeError) -> Error {
+    Error::TypeMismatch(format!("tensor data does not match its shape: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::{arr2, Array2};
+
+    use super::*;
+    use crate::context::{ElementKind, Quantization, TensorInfo};
+
+    #[test]
+    fn owned_tensor_arrays() {
+        let array = arr2(&[[1.0f32, 2.0, 3.0], [4.0, 5.0, 6.0]]);
+        let value = TensorValue::from_array(&array.t()).unwrap();
+        assert_eq!(value.dims(), [3, 2]);
+
+        let tensor = OwnedTensor {
+            info: TensorInfo {
+                name: "t".to_string(),
+                element_kind: ElementKind::kTfLiteFloat32,
+                dims: value.dims().to_vec(),
+                quantization: Quantization::None,
+            },
+            data: value.as_bytes().to_vec(),
+        };
+        let copy: Array2<f32> = tensor.to_array().unwrap().into_dimensionality().unwrap();
+        assert_eq!(copy, array.t());
+        assert!(tensor.to_array::<i32>().is_err());
+    }
+}
@@ -1,25 +1,79 @@
-use maybe_owned::MaybeOwned;
+use libc::c_int;
 
+use super::delegate::OwnedDelegate;
+use super::error_reporter::ErrorReporter;
 use super::op_resolver::OpResolver;
-use super::FlatBufferModel;
 use super::Interpreter;
+use super::ModelRef;
 use crate::bindings::tflite as bindings;
+use crate::bindings::TfLiteStatus;
 use crate::{Error, Result};
 
 cpp! {{
     #include "tensorflow/lite/model.h"
     #include "tensorflow/lite/kernels/register.h"
+    #include "tensorflow/lite/schema/schema_utils.h"
 
     using namespace tflite;
 }}
 
+/// Options of the interpreters built by an `InterpreterBuilder`.
+///
+/// More options may be added, so set them with the methods of the same name, e.g.
+/// `InterpreterOptions::default().preserve_all_tensors(true)`.
+#[derive(Debug, Clone, Copy, Default)]
+#[non_exhaustive]
+pub struct InterpreterOptions {
+    /// Gives every tensor its own memory instead of reusing the arena for intermediate
+    /// tensors, so that all tensors can be read after `invoke`. This uses more memory,
+    /// and nodes run by a delegate may still not write their intermediate tensors.
+    pub preserve_all_tensors: bool,
+    /// Releases the memory of dynamic intermediate tensors as soon as they are no longer
+    /// used, see `Interpreter::ensure_dynamic_tensors_are_released`.
+    pub ensure_dynamic_tensors_are_released: bool,
+    /// Allocates tensors of at least this many bytes dynamically instead of in the arena,
+    /// which lowers the peak memory of models with large intermediate tensors.
+    /// Thresholds beyond `c_int::MAX` are clamped to it.
+    pub dynamic_allocation_for_large_tensors: Option<usize>,
+    /// Keeps the order of the nodes when the graph is partitioned for delegates, at the cost
+    /// of smaller delegated partitions.
+    pub disable_delegate_clustering: bool,
+}
+
+impl InterpreterOptions {
+    pub fn preserve_all_tensors(mut self, preserve_all_tensors: bool) -> Self {
+        self.preserve_all_tensors = preserve_all_tensors;
+        self
+    }
+
+    pub fn ensure_dynamic_tensors_are_released(mut self, release: bool) -> Self {
+        self.ensure_dynamic_tensors_are_released = release;
+        self
+    }
+
+    pub fn dynamic_allocation_for_large_tensors(mut self, threshold: Option<usize>) -> Self {
+        self.dynamic_allocation_for_large_tensors = threshold;
+        self
+    }
+
+    pub fn disable_delegate_clustering(mut self, disable: bool) -> Self {
+        self.disable_delegate_clustering = disable;
+        self
+    }
+}
+
 pub struct InterpreterBuilder<'a, Op>
 where
     Op: OpResolver,
 {
-    handle: Box<bindings::InterpreterBuilder>,
-    _model: MaybeOwned<'a, FlatBufferModel>,
+    /// Created by `build`, as `tflite::InterpreterBuilder` copies its options.
+    handle: Option<Box<bindings::InterpreterBuilder>>,
+    _model: ModelRef<'a>,
     _resolver: Op,
+    delegates: Vec<Box<dyn OwnedDelegate>>,
+    options: InterpreterOptions,
+    /// Collects the messages of the builder and of the interpreters it builds.
+    error_reporter: ErrorReporter,
 }
 
 impl<'a, Op> Drop for InterpreterBuilder<'a, Op>
@@ -27,7 +81,10 @@ where
     Op: OpResolver,
 {
     fn drop(&mut self) {
-        let handle = Box::into_raw(std::mem::take(&mut self.handle));
+        let Some(handle) = self.handle.take() else {
+            return;
+        };
+        let handle = Box::into_raw(handle);
         #[allow(clippy::forgetting_copy_types, clippy::useless_transmute, deprecated)]
         unsafe {
             cpp!([handle as "InterpreterBuilder*"] {
@@ -42,66 +99,165 @@ where
     Op: OpResolver,
 {
     #[allow(clippy::new_ret_no_self)]
-    pub fn new<M: Into<MaybeOwned<'a, FlatBufferModel>>>(model: M, resolver: Op) -> Result<Self> {
-        use std::ops::Deref;
-        let model = model.into();
-        let handle = {
-            let model_handle = model.as_ref().handle.deref();
-            let resolver_handle = resolver.get_resolver_handle();
+    pub fn new<M: Into<ModelRef<'a>>>(model: M, resolver: Op) -> Result<Self> {
+        Ok(Self {
+            handle: None,
+            _model: model.into(),
+            _resolver: resolver,
+            delegates: Vec::new(),
+            options: InterpreterOptions::default(),
+            error_reporter: ErrorReporter::default(),
+        })
+    }
 
-            #[allow(clippy::forgetting_copy_types, deprecated)]
-            unsafe {
-                cpp!([model_handle as "const FlatBufferModel*",
-                    resolver_handle as "const OpResolver*"
-                ] -> *mut bindings::InterpreterBuilder as "InterpreterBuilder*" {
-                    return new InterpreterBuilder(*model_handle, *resolver_handle);
-                })
-            }
+    /// Adds a delegate that is applied to the graph when the interpreter is built.
+    pub fn add_delegate<D: OwnedDelegate + 'static>(mut self, delegate: D) -> Self {
+        self.delegates.push(Box::new(delegate));
+        self
+    }
+
+    /// Sets the options of the interpreter, replacing the previous ones.
+    pub fn with_options(mut self, options: InterpreterOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Builds the interpreter and allocates its tensors.
+    ///
+    /// Fails with `Error::UnsupportedOp` if the resolver lacks an operator of the model,
+    /// or with `Error::ModelVerification` if the graph is invalid.
+    pub fn build(mut self) -> Result<Interpreter<'a, Op>> {
+        let mut status = TfLiteStatus::kTfLiteOk;
+        let builder = self.create_handle()?;
+
+        #[allow(clippy::forgetting_copy_types, deprecated)]
+        let handle = unsafe {
+            cpp!([builder as "InterpreterBuilder*", mut status as "TfLiteStatus"] -> *mut bindings::Interpreter as "Interpreter*" {
+                std::unique_ptr<Interpreter> interpreter;
+                status = (*builder)(&interpreter);
+                return interpreter.release();
+            })
         };
         if handle.is_null() {
-            return Err(Error::InternalError("failed to create InterpreterBuilder".to_string()));
+            return Err(self.build_error(status, "failed to build"));
         }
-        let handle = unsafe { Box::from_raw(handle) };
-        Ok(Self { handle, _model: model, _resolver: resolver })
+        Interpreter::new(handle, self)
     }
 
-    pub fn build(mut self) -> Result<Interpreter<'a, Op>> {
-        #[allow(clippy::forgetting_copy_types, deprecated)]
-        let handle = {
-            let builder = &mut *self.handle;
-            unsafe {
-                cpp!([builder as "InterpreterBuilder*"] -> *mut bindings::Interpreter as "Interpreter*" {
-                    std::unique_ptr<Interpreter> interpreter;
-                    (*builder)(&interpreter);
-                    return interpreter.release();
-                })
-            }
+    pub fn build_with_threads(mut self, threads: c_int) -> Result<Interpreter<'a, Op>> {
+        let mut status = TfLiteStatus::kTfLiteOk;
+        let builder = self.create_handle()?;
+
+        #[allow(clippy::forgetting_copy_types, deprecated, clippy::transmute_num_to_bytes)]
+        let handle = unsafe {
+            cpp!([builder as "InterpreterBuilder*", threads as "int", mut status as "TfLiteStatus"] -> *mut bindings::Interpreter as "Interpreter*" {
+                std::unique_ptr<Interpreter> interpreter;
+                status = (*builder)(&interpreter, threads);
+                return interpreter.release();
+            })
         };
         if handle.is_null() {
-            return Err(Error::InternalError("failed to build".to_string()));
+            return Err(self.build_error(status, "failed to build with threads"));
         }
         Interpreter::new(handle, self)
     }
 
-    pub fn build_with_threads(
-        mut self,
-        threads: std::os::raw::c_int,
-    ) -> Result<Interpreter<'a, Op>> {
+    /// Classifies a failed build: `tflite::InterpreterBuilder` fails with `kTfLiteError`
+    /// when a builtin operator is missing, so the resolver is asked for the operators of
+    /// the model. Missing custom operators only fail once the tensors are allocated.
+    fn build_error(&self, status: TfLiteStatus, summary: &str) -> Error {
+        let model_handle = &*self._model.handle;
+        let resolver_handle = self._resolver.get_resolver_handle();
+
         #[allow(clippy::forgetting_copy_types, deprecated)]
-        let handle = {
-            let builder = &mut *self.handle;
-            #[allow(clippy::transmute_num_to_bytes)]
-            unsafe {
-                cpp!([builder as "InterpreterBuilder*", threads as "int"] -> *mut bindings::Interpreter as "Interpreter*" {
-                    std::unique_ptr<Interpreter> interpreter;
-                    (*builder)(&interpreter, threads);
-                    return interpreter.release();
-                })
-            }
+        let missing_op = unsafe {
+            cpp!([model_handle as "const FlatBufferModel*",
+                resolver_handle as "const OpResolver*"
+            ] -> bool as "bool" {
+                const auto* opcodes = model_handle->GetModel()->operator_codes();
+                if (opcodes == nullptr) {
+                    return false;
+                }
+                for (const OperatorCode* opcode : *opcodes) {
+                    BuiltinOperator builtin_code = GetBuiltinCode(opcode);
+                    if (builtin_code != BuiltinOperator_CUSTOM
+                        && resolver_handle->FindOp(builtin_code, opcode->version()) == nullptr) {
+                        return true;
+                    }
+                }
+                return false;
+            })
+        };
+        let kind: fn(String) -> Error =
+            if missing_op { Error::UnsupportedOp } else { Error::ModelVerification };
+        self.error_reporter.status_error(status, kind, summary)
+    }
+
+    /// Creates the `tflite::InterpreterBuilder` with the options and delegates.
+    fn create_handle(&mut self) -> Result<*mut bindings::InterpreterBuilder> {
+        use std::ops::Deref;
+        let model_handle = self._model.handle.deref();
+        let resolver_handle = self._resolver.get_resolver_handle();
+        let reporter = self.error_reporter.handle();
+        let InterpreterOptions {
+            preserve_all_tensors,
+            ensure_dynamic_tensors_are_released,
+            dynamic_allocation_for_large_tensors,
+            disable_delegate_clustering,
+        } = self.options;
+        // TensorFlow Lite disables the option with `0`.
+        let dynamic_allocation_for_large_tensors = dynamic_allocation_for_large_tensors
+            .map_or(0, |threshold| threshold.clamp(1, c_int::MAX as usize) as c_int);
+
+        #[allow(clippy::forgetting_copy_types, deprecated, clippy::transmute_num_to_bytes)]
+        let handle = unsafe {
+            cpp!([model_handle as "const FlatBufferModel*",
+                resolver_handle as "const OpResolver*",
+                reporter as "ErrorReporter*",
+                preserve_all_tensors as "bool",
+                ensure_dynamic_tensors_are_released as "bool",
+                dynamic_allocation_for_large_tensors as "int",
+                disable_delegate_clustering as "bool"
+            ] -> *mut bindings::InterpreterBuilder as "InterpreterBuilder*" {
+                InterpreterOptions options;
+                options.SetPreserveAllTensors(preserve_all_tensors);
+                options.SetEnsureDynamicTensorsAreReleased(ensure_dynamic_tensors_are_released);
+                options.SetDynamicAllocationForLargeTensors(dynamic_allocation_for_large_tensors);
+                options.SetDisableDelegateClustering(disable_delegate_clustering);
+                return new InterpreterBuilder(model_handle->GetModel(), *resolver_handle,
+                    reporter, &options, model_handle->allocation());
+            })
         };
         if handle.is_null() {
-            return Err(Error::InternalError("failed to build with threads".to_string()));
+            return Err(Error::Allocation("failed to create InterpreterBuilder".to_string()));
         }
-        Interpreter::new(handle, self)
+        self.handle = Some(unsafe { Box::from_raw(handle) });
+
+        for delegate in &self.delegates {
+            let delegate_handle = delegate.get_delegate_handle();
+
+            #[allow(clippy::forgetting_copy_types, deprecated)]
+            unsafe {
+                cpp!([handle as "InterpreterBuilder*", delegate_handle as "TfLiteDelegate*"] {
+                    handle->AddDelegate(delegate_handle);
+                })
+            };
+        }
+        Ok(handle)
+    }
+
+    pub(crate) fn error_reporter(&self) -> &ErrorReporter {
+        &self.error_reporter
+    }
+}
+
+impl InterpreterBuilder<'static, Box<dyn OpResolver>> {
+    /// Creates the builder of a `DynInterpreter`, boxing `resolver`.
+    pub fn new_dyn<M, R>(model: M, resolver: R) -> Result<Self>
+    where
+        M: Into<ModelRef<'static>>,
+        R: OpResolver + 'static,
+    {
+        Self::new(model, Box::new(resolver))
     }
 }
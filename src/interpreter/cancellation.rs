@@ -0,0 +1,174 @@
+use std::collections::BTreeMap;
+use std::ffi::c_void;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex, OnceLock};
+use std::thread;
+use std::time::Instant;
+
+cpp! {{
+    #include "tensorflow/lite/interpreter.h"
+
+    static bool CancellationCheck(void* data) {
+        return rust!(Cancellation_check [data: *const Cancellation as "void*"] -> bool as "bool" {
+            let cancellation = unsafe { &*data };
+            let cancelled = cancellation.is_cancelled();
+            if cancelled {
+                cancellation.aborted.store(true, Ordering::SeqCst);
+            }
+            cancelled
+        });
+    }
+}}
+
+/// Aborts the `invoke` of the interpreter it is attached to from another thread, see
+/// `Interpreter::set_cancellation_token`.
+///
+/// TensorFlow Lite checks the token between operators, so an operator that already runs
+/// is finished first. Once cancelled, the token cancels every `invoke` until it is reset.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    pub fn reset(&self) {
+        self.cancelled.store(false, Ordering::SeqCst);
+    }
+}
+
+/// The tokens checked by the cancellation function of an interpreter.
+#[derive(Default)]
+pub(crate) struct Cancellation {
+    pub(crate) token: Option<CancellationToken>,
+    /// Cancelled when the timeout of `invoke_with_timeout` expires.
+    pub(crate) timeout: CancellationToken,
+    /// Set when the cancellation function stopped an invocation.
+    aborted: AtomicBool,
+}
+
+impl Cancellation {
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.timeout.is_cancelled() || self.token.as_ref().is_some_and(|t| t.is_cancelled())
+    }
+
+    /// Returns whether the cancellation function stopped an invocation since the last call.
+    pub(crate) fn take_aborted(&self) -> bool {
+        self.aborted.swap(false, Ordering::SeqCst)
+    }
+
+    /// Makes `interpreter` check `self`, which must outlive it, between operators.
+    pub(crate) fn install(&self, interpreter: *mut c_void) {
+        let data = self as *const Cancellation as *mut c_void;
+
+        #[allow(clippy::forgetting_copy_types, deprecated)]
+        unsafe {
+            cpp!([interpreter as "tflite::Interpreter*", data as "void*"] {
+                interpreter->SetCancellationFunction(data, CancellationCheck);
+            })
+        };
+    }
+}
+
+/// Cancels the tokens of `invoke_with_timeout` once their deadline passes, from a single
+/// thread shared by all interpreters.
+#[derive(Default)]
+struct Watchdog {
+    deadlines: Mutex<Deadlines>,
+    changed: Condvar,
+}
+
+#[derive(Default)]
+struct Deadlines {
+    next_id: u64,
+    tokens: BTreeMap<(Instant, u64), CancellationToken>,
+}
+
+impl Watchdog {
+    /// Returns the watchdog, starting its thread on first use.
+    fn get() -> &'static Watchdog {
+        static WATCHDOG: OnceLock<Watchdog> = OnceLock::new();
+        WATCHDOG.get_or_init(|| {
+            // The thread waits for the initialization to finish before it runs.
+            thread::Builder::new()
+                .name("tflite-watchdog".to_string())
+                .spawn(|| Watchdog::get().run())
+                .expect("failed to spawn the watchdog thread");
+            Watchdog::default()
+        })
+    }
+
+    fn run(&self) -> ! {
+        let mut deadlines = self.deadlines.lock().unwrap();
+        loop {
+            let now = Instant::now();
+            while let Some(entry) = deadlines.tokens.first_entry() {
+                if entry.key().0 > now {
+                    break;
+                }
+                entry.remove().cancel();
+            }
+            deadlines = match deadlines.tokens.keys().next() {
+                Some(&(deadline, _)) => {
+                    self.changed.wait_timeout(deadlines, deadline - now).unwrap().0
+                }
+                None => self.changed.wait(deadlines).unwrap(),
+            };
+        }
+    }
+}
+
+/// Cancels a token at a deadline unless it is dropped first, see `Deadline::schedule`.
+pub(crate) struct Deadline {
+    key: (Instant, u64),
+}
+
+impl Deadline {
+    /// Makes the watchdog cancel `token` at `deadline`.
+    pub(crate) fn schedule(token: CancellationToken, deadline: Instant) -> Self {
+        let watchdog = Watchdog::get();
+        let mut deadlines = watchdog.deadlines.lock().unwrap();
+        let key = (deadline, deadlines.next_id);
+        deadlines.next_id += 1;
+        deadlines.tokens.insert(key, token);
+        watchdog.changed.notify_one();
+        Self { key }
+    }
+}
+
+impl Drop for Deadline {
+    fn drop(&mut self) {
+        Watchdog::get().deadlines.lock().unwrap().tokens.remove(&self.key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn deadlines() {
+        let (late, early, dropped) =
+            (CancellationToken::new(), CancellationToken::new(), CancellationToken::new());
+        let now = Instant::now();
+        let _late = Deadline::schedule(late.clone(), now + Duration::from_secs(60));
+        let _early = Deadline::schedule(early.clone(), now + Duration::from_millis(10));
+        drop(Deadline::schedule(dropped.clone(), now + Duration::from_millis(10)));
+
+        thread::sleep(Duration::from_millis(200));
+        assert!(early.is_cancelled());
+        assert!(!late.is_cancelled() && !dropped.is_cancelled());
+    }
+}
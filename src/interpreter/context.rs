@@ -1,7 +1,14 @@
-use std::ffi::CStr;
-use std::fmt;
+use std::ffi::{CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::{fmt, mem, slice};
 
-use crate::bindings;
+use super::node::int_array;
+use super::{tensor_data_ptr, TensorIndex};
+use crate::{bindings, Error, Result};
+
+cpp! {{
+    #include "tensorflow/lite/core/c/common.h"
+}}
 
 pub type ElementKind = bindings::TfLiteType;
 pub type QuantizationParams = bindings::TfLiteQuantizationParams;
@@ -10,28 +17,202 @@ pub trait ElemKindOf {
     fn elem_kind_of() -> ElementKind;
 }
 
-impl ElemKindOf for f32 {
-    fn elem_kind_of() -> ElementKind {
-        bindings::TfLiteType::kTfLiteFloat32
-    }
+macro_rules! impl_elem_kind_of {
+    ($($t:ty => $kind:ident,)*) => ($(
+        impl ElemKindOf for $t {
+            fn elem_kind_of() -> ElementKind {
+                bindings::TfLiteType::$kind
+            }
+        }
+    )*)
+}
+
+impl_elem_kind_of! {
+    f32 => kTfLiteFloat32,
+    f64 => kTfLiteFloat64,
+    u8 => kTfLiteUInt8,
+    u16 => kTfLiteUInt16,
+    u32 => kTfLiteUInt32,
+    u64 => kTfLiteUInt64,
+    i8 => kTfLiteInt8,
+    i16 => kTfLiteInt16,
+    i32 => kTfLiteInt32,
+    i64 => kTfLiteInt64,
+    Complex64 => kTfLiteComplex64,
+    Complex128 => kTfLiteComplex128,
 }
 
-impl ElemKindOf for u8 {
-    fn elem_kind_of() -> ElementKind {
-        bindings::TfLiteType::kTfLiteUInt8
+#[cfg(feature = "half")]
+impl_elem_kind_of! {
+    half::f16 => kTfLiteFloat16,
+    half::bf16 => kTfLiteBFloat16,
+}
+
+/// Element of a `kTfLiteComplex64` tensor, laid out like `std::complex<float>`.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Complex64 {
+    pub re: f32,
+    pub im: f32,
+}
+
+/// Element of a `kTfLiteComplex128` tensor, laid out like `std::complex<double>`.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Complex128 {
+    pub re: f64,
+    pub im: f64,
+}
+
+/// Fails unless `T` is the element type stored in a tensor of kind `element_kind`.
+///
+/// `kTfLiteBool` tensors are accessed as `u8`, since nothing guarantees that their
+/// bytes are valid `bool`s, e.g. before the tensor is first written.
+pub(crate) fn check_elem_kind_of<T: ElemKindOf>(element_kind: ElementKind) -> Result<()> {
+    let as_u8 =
+        element_kind == ElementKind::kTfLiteBool && T::elem_kind_of() == ElementKind::kTfLiteUInt8;
+    if element_kind != T::elem_kind_of() && !as_u8 {
+        return Err(Error::TypeMismatch(format!(
+            "Invalid type reference of `{:?}` to the original type `{:?}`",
+            T::elem_kind_of(),
+            element_kind
+        )));
     }
+    Ok(())
+}
+
+/// Element types of quantized tensors.
+pub trait QuantizedElem: ElemKindOf + Copy {
+    fn to_i32(self) -> i32;
+    /// Converts `value`, saturating at the bounds of `Self`.
+    fn from_i32_saturating(value: i32) -> Self;
 }
 
-impl ElemKindOf for i32 {
-    fn elem_kind_of() -> ElementKind {
-        bindings::TfLiteType::kTfLiteInt32
+macro_rules! impl_quantized_elem {
+    ($($t:ty)*) => ($(
+        impl QuantizedElem for $t {
+            fn to_i32(self) -> i32 {
+                self as i32
+            }
+
+            fn from_i32_saturating(value: i32) -> Self {
+                value.clamp(<$t>::MIN as i32, <$t>::MAX as i32) as $t
+            }
+        }
+    )*)
+}
+
+impl_quantized_elem! { u8 i8 i16 }
+
+/// Affine quantization of a tensor: `real_value = scale * (quantized_value - zero_point)`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Quantization {
+    None,
+    PerTensor {
+        scale: f32,
+        zero_point: i32,
+    },
+    /// The `i`-th scale and zero point apply to the `i`-th slice along `quantized_dimension`.
+    PerChannel {
+        scales: Vec<f32>,
+        zero_points: Vec<i32>,
+        quantized_dimension: usize,
+    },
+}
+
+impl Quantization {
+    pub fn is_quantized(&self) -> bool {
+        *self != Quantization::None
+    }
+
+    /// Returns the scale and zero point applying to the element at `flat_index`
+    /// of a tensor with dimensions `dims`.
+    pub fn params_at(&self, dims: &[usize], flat_index: usize) -> Option<(f32, i32)> {
+        match self {
+            Quantization::None => None,
+            Quantization::PerTensor { scale, zero_point } => Some((*scale, *zero_point)),
+            Quantization::PerChannel { scales, zero_points, quantized_dimension } => {
+                let channels = *dims.get(*quantized_dimension)?;
+                let stride: usize = dims[quantized_dimension + 1..].iter().product();
+                let channel = (flat_index / stride.max(1)) % channels.max(1);
+                let zero_point = zero_points.get(channel).copied().unwrap_or_default();
+                Some((*scales.get(channel)?, zero_point))
+            }
+        }
+    }
+
+    /// Quantizes `values` laid out with dimensions `dims`.
+    pub fn quantize<T: QuantizedElem>(&self, dims: &[usize], values: &[f32]) -> Result<Vec<T>> {
+        values
+            .iter()
+            .enumerate()
+            .map(|(i, &value)| {
+                let (scale, zero_point) = self.checked_params_at(dims, i)?;
+                Ok(T::from_i32_saturating(
+                    ((value / scale).round() as i32).saturating_add(zero_point),
+                ))
+            })
+            .collect()
+    }
+
+    /// Dequantizes `values` laid out with dimensions `dims`.
+    pub fn dequantize<T: QuantizedElem>(&self, dims: &[usize], values: &[T]) -> Result<Vec<f32>> {
+        values
+            .iter()
+            .enumerate()
+            .map(|(i, &value)| {
+                let (scale, zero_point) = self.checked_params_at(dims, i)?;
+                Ok(scale * (value.to_i32() - zero_point) as f32)
+            })
+            .collect()
+    }
+
+    fn checked_params_at(&self, dims: &[usize], flat_index: usize) -> Result<(f32, i32)> {
+        self.params_at(dims, flat_index)
+            .ok_or_else(|| Error::TypeMismatch("tensor has no quantization parameters".to_string()))
+    }
+
+    fn from_tensor(t: &bindings::TfLiteTensor) -> Self {
+        if t.quantization.type_ == bindings::TfLiteQuantizationType::kTfLiteAffineQuantization
+            && !t.quantization.params.is_null()
+        {
+            let (scales, zero_points, quantized_dimension) = unsafe {
+                let params = &*(t.quantization.params as *const bindings::TfLiteAffineQuantization);
+                let scales = match params.scale.as_ref() {
+                    Some(scale) => scale.data.as_slice(scale.size as usize).to_vec(),
+                    None => Vec::new(),
+                };
+                let zero_points = match params.zero_point.as_ref() {
+                    Some(zero_point) => zero_point.data.as_slice(zero_point.size as usize).to_vec(),
+                    None => Vec::new(),
+                };
+                (scales, zero_points, params.quantized_dimension as usize)
+            };
+            match scales.len() {
+                0 => {}
+                1 => {
+                    return Quantization::PerTensor {
+                        scale: scales[0],
+                        zero_point: zero_points.first().copied().unwrap_or_default(),
+                    }
+                }
+                _ => return Quantization::PerChannel { scales, zero_points, quantized_dimension },
+            }
+        }
+        if t.params.scale != 0.0 {
+            Quantization::PerTensor { scale: t.params.scale, zero_point: t.params.zero_point }
+        } else {
+            Quantization::None
+        }
     }
 }
 
+#[derive(Clone)]
 pub struct TensorInfo {
     pub name: String,
     pub element_kind: ElementKind,
     pub dims: Vec<usize>,
+    pub quantization: Quantization,
 }
 
 impl fmt::Debug for TensorInfo {
@@ -40,6 +221,7 @@ impl fmt::Debug for TensorInfo {
             .field("name", &self.name)
             .field("element_kind", &self.element_kind)
             .field("dims", &self.dims)
+            .field("quantization", &self.quantization)
             .finish()
     }
 }
@@ -47,7 +229,11 @@ impl fmt::Debug for TensorInfo {
 impl<'a> From<&'a bindings::TfLiteTensor> for TensorInfo {
     fn from(t: &'a bindings::TfLiteTensor) -> Self {
         Self {
-            name: unsafe { CStr::from_ptr(t.name) }.to_str().unwrap().to_string(),
+            name: if t.name.is_null() {
+                String::new()
+            } else {
+                unsafe { CStr::from_ptr(t.name) }.to_str().unwrap().to_string()
+            },
             element_kind: t.type_,
             dims: {
                 let slice = unsafe {
@@ -56,6 +242,148 @@ impl<'a> From<&'a bindings::TfLiteTensor> for TensorInfo {
                 };
                 slice.iter().map(|n| *n as usize).collect()
             },
+            quantization: Quantization::from_tensor(t),
         }
     }
 }
+
+/// Read-only view of a tensor, as handed to delegate kernels.
+pub struct TensorView<'a> {
+    index: TensorIndex,
+    inner: &'a bindings::TfLiteTensor,
+}
+
+impl<'a> TensorView<'a> {
+    pub(crate) fn new(index: TensorIndex, inner: &'a bindings::TfLiteTensor) -> Self {
+        Self { index, inner }
+    }
+
+    /// Index of the tensor in the interpreter.
+    pub fn index(&self) -> TensorIndex {
+        self.index
+    }
+
+    pub fn info(&self) -> TensorInfo {
+        self.inner.into()
+    }
+
+    pub fn data<T>(&self) -> Result<&'a [T]>
+    where
+        T: ElemKindOf,
+    {
+        check_elem_kind_of::<T>(self.inner.type_)?;
+        let ptr = tensor_data_ptr::<T>(self.inner)?;
+
+        Ok(unsafe { slice::from_raw_parts(ptr, self.inner.bytes / mem::size_of::<T>()) })
+    }
+}
+
+/// Writable view of a tensor, as handed to delegate kernels.
+pub struct TensorViewMut<'a> {
+    index: TensorIndex,
+    inner: &'a mut bindings::TfLiteTensor,
+}
+
+impl<'a> TensorViewMut<'a> {
+    pub(crate) fn new(index: TensorIndex, inner: &'a mut bindings::TfLiteTensor) -> Self {
+        Self { index, inner }
+    }
+
+    /// Index of the tensor in the interpreter.
+    pub fn index(&self) -> TensorIndex {
+        self.index
+    }
+
+    pub fn info(&self) -> TensorInfo {
+        (&*self.inner).into()
+    }
+
+    pub fn data<T>(&self) -> Result<&[T]>
+    where
+        T: ElemKindOf,
+    {
+        check_elem_kind_of::<T>(self.inner.type_)?;
+        let ptr = tensor_data_ptr::<T>(self.inner)?;
+
+        Ok(unsafe { slice::from_raw_parts(ptr, self.inner.bytes / mem::size_of::<T>()) })
+    }
+
+    pub fn data_mut<T>(&mut self) -> Result<&mut [T]>
+    where
+        T: ElemKindOf,
+    {
+        check_elem_kind_of::<T>(self.inner.type_)?;
+        let ptr = tensor_data_ptr::<T>(self.inner)?;
+
+        Ok(unsafe { slice::from_raw_parts_mut(ptr, self.inner.bytes / mem::size_of::<T>()) })
+    }
+}
+
+/// Runs `f`, reporting errors and panics to `context` since neither may cross into C++.
+pub(crate) unsafe fn guarded<T>(
+    context: *mut bindings::TfLiteContext,
+    f: impl FnOnce() -> Result<T>,
+) -> Option<T> {
+    let message = match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(value)) => return Some(value),
+        Ok(Err(e)) => e.to_string(),
+        Err(_) => "Rust callback panicked".to_string(),
+    };
+    let message = CString::new(message).unwrap_or_default();
+    let message_ptr = message.as_ptr();
+
+    #[allow(clippy::forgetting_copy_types, deprecated)]
+    cpp!([context as "TfLiteContext*", message_ptr as "const char*"] {
+        context->ReportError(context, "%s", message_ptr);
+    });
+    None
+}
+
+/// Returns views of the input and output tensors of `node`, skipping omitted optional tensors.
+pub(crate) unsafe fn node_tensors<'a>(
+    context: *mut bindings::TfLiteContext,
+    node: &bindings::TfLiteNode,
+) -> Result<(Vec<TensorView<'a>>, Vec<TensorViewMut<'a>>)> {
+    let tensors = (*context).tensors;
+    let inputs = node_tensor_indices(context, node.inputs)?;
+    let outputs = node_tensor_indices(context, node.outputs)?;
+    // The output views are mutable, so they may not alias each other or an input.
+    for (i, index) in outputs.iter().enumerate() {
+        if inputs.contains(index) {
+            return Err(Error::InvalidIndex("node output is also an input".to_string()));
+        }
+        if outputs[..i].contains(index) {
+            return Err(Error::InvalidIndex("node output is repeated".to_string()));
+        }
+    }
+    let inputs = inputs
+        .into_iter()
+        .map(|index| TensorView::new(index, &*tensors.add(index as usize)))
+        .collect();
+    let outputs = outputs
+        .into_iter()
+        .map(|index| TensorViewMut::new(index, &mut *tensors.add(index as usize)))
+        .collect();
+    Ok((inputs, outputs))
+}
+
+/// Returns the tensor indices of `array`, an input or output list of a node of `context`,
+/// skipping omitted optional tensors and failing on an index outside of the context.
+pub(crate) unsafe fn node_tensor_indices(
+    context: *const bindings::TfLiteContext,
+    array: *const bindings::TfLiteIntArray,
+) -> Result<Vec<TensorIndex>> {
+    let tensors_size = (*context).tensors_size;
+    int_array(array)
+        .iter()
+        .copied()
+        .filter(|&index| index >= 0)
+        .map(|index| {
+            if (index as usize) < tensors_size {
+                Ok(index)
+            } else {
+                Err(Error::InvalidIndex(format!("node tensor {index} does not exist")))
+            }
+        })
+        .collect()
+}
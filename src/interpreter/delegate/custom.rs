@@ -0,0 +1,385 @@
+use std::ffi::c_void;
+use std::panic::{self, AssertUnwindSafe};
+use std::{ptr, slice};
+
+use libc::{c_int, size_t};
+
+use super::{OwnedDelegate, SysDelegate};
+use crate::bindings::{
+    TfLiteContext, TfLiteIntArray, TfLiteNode, TfLiteRegistration, TfLiteTensor,
+};
+use crate::interpreter::context::{guarded, node_tensors, TensorView, TensorViewMut};
+use crate::interpreter::node::{int_array, NodeInfo};
+use crate::interpreter::TensorIndex;
+use crate::{Error, Result};
+
+pub type BufferHandle = crate::bindings::TfLiteBufferHandle;
+
+cpp! {{
+    #include <algorithm>
+
+    #include "tensorflow/lite/builtin_ops.h"
+    #include "tensorflow/lite/core/c/common.h"
+
+    static void* CustomDelegateKernelInit(TfLiteContext* context, const char* buffer, size_t) {
+        const TfLiteDelegateParams* params = reinterpret_cast<const TfLiteDelegateParams*>(buffer);
+        const TfLiteDelegate* delegate = params->delegate;
+        const TfLiteIntArray* nodes = params->nodes_to_replace;
+        const TfLiteIntArray* inputs = params->input_tensors;
+        const TfLiteIntArray* outputs = params->output_tensors;
+        return rust!(CustomDelegate_init_kernel [
+            context: *mut TfLiteContext as "TfLiteContext*",
+            delegate: *const SysDelegate as "const TfLiteDelegate*",
+            nodes: *const TfLiteIntArray as "const TfLiteIntArray*",
+            inputs: *const TfLiteIntArray as "const TfLiteIntArray*",
+            outputs: *const TfLiteIntArray as "const TfLiteIntArray*"
+        ] -> *mut c_void as "void*" {
+            unsafe { init_kernel(context, delegate, nodes, inputs, outputs) }
+        });
+    }
+
+    static void CustomDelegateKernelFree(TfLiteContext*, void* buffer) {
+        rust!(CustomDelegate_free_kernel [buffer: *mut c_void as "void*"] {
+            unsafe { free_kernel(buffer) }
+        });
+    }
+
+    static TfLiteStatus CustomDelegateKernelPrepare(TfLiteContext* context, TfLiteNode* node) {
+        bool ok = rust!(CustomDelegate_prepare_kernel [
+            context: *mut TfLiteContext as "TfLiteContext*",
+            node: *mut TfLiteNode as "TfLiteNode*"
+        ] -> bool as "bool" {
+            unsafe { run_kernel(context, node, false) }
+        });
+        return ok ? kTfLiteOk : kTfLiteError;
+    }
+
+    static TfLiteStatus CustomDelegateKernelInvoke(TfLiteContext* context, TfLiteNode* node) {
+        bool ok = rust!(CustomDelegate_invoke_kernel [
+            context: *mut TfLiteContext as "TfLiteContext*",
+            node: *mut TfLiteNode as "TfLiteNode*"
+        ] -> bool as "bool" {
+            unsafe { run_kernel(context, node, true) }
+        });
+        return ok ? kTfLiteOk : kTfLiteError;
+    }
+
+    static TfLiteRegistration CustomDelegateKernel() {
+        TfLiteRegistration registration{};
+        registration.init = CustomDelegateKernelInit;
+        registration.free = CustomDelegateKernelFree;
+        registration.prepare = CustomDelegateKernelPrepare;
+        registration.invoke = CustomDelegateKernelInvoke;
+        registration.builtin_code = kTfLiteBuiltinDelegate;
+        registration.custom_name = "CustomDelegate";
+        registration.version = 1;
+        return registration;
+    }
+
+    static TfLiteStatus CustomDelegatePrepare(TfLiteContext* context, TfLiteDelegate* delegate) {
+        bool ok = rust!(CustomDelegate_prepare [
+            context: *mut TfLiteContext as "TfLiteContext*",
+            delegate: *mut SysDelegate as "TfLiteDelegate*"
+        ] -> bool as "bool" {
+            unsafe { prepare(context, delegate) }
+        });
+        return ok ? kTfLiteOk : kTfLiteError;
+    }
+
+    static TfLiteStatus CustomDelegateCopyFromBufferHandle(
+        TfLiteContext* context,
+        TfLiteDelegate* delegate,
+        TfLiteBufferHandle buffer_handle,
+        TfLiteTensor* tensor
+    ) {
+        bool ok = rust!(CustomDelegate_copy_from_buffer_handle [
+            context: *mut TfLiteContext as "TfLiteContext*",
+            delegate: *mut SysDelegate as "TfLiteDelegate*",
+            buffer_handle: BufferHandle as "TfLiteBufferHandle",
+            tensor: *mut TfLiteTensor as "TfLiteTensor*"
+        ] -> bool as "bool" {
+            unsafe { copy_from_buffer_handle(context, delegate, buffer_handle, tensor) }
+        });
+        return ok ? kTfLiteOk : kTfLiteError;
+    }
+
+    static void CustomDelegateFreeBufferHandle(
+        TfLiteContext*,
+        TfLiteDelegate* delegate,
+        TfLiteBufferHandle* handle
+    ) {
+        TfLiteBufferHandle buffer_handle = *handle;
+        rust!(CustomDelegate_free_buffer_handle [
+            delegate: *mut SysDelegate as "TfLiteDelegate*",
+            buffer_handle: BufferHandle as "TfLiteBufferHandle"
+        ] {
+            unsafe { free_buffer_handle(delegate, buffer_handle) }
+        });
+        *handle = kTfLiteNullBufferHandle;
+    }
+}}
+
+/// A delegate implemented in Rust.
+///
+/// During graph preparation the delegate claims the nodes it supports; every connected
+/// partition of claimed nodes is then replaced by a single node running a `DelegateKernel`.
+/// Wrap it in a `CustomDelegate` to apply it with `Interpreter::modify_graph_with_delegate`.
+pub trait Delegate: Send + Sync + 'static {
+    /// Returns whether the delegate takes over `node`.
+    /// `tensors` holds all tensors of the graph, indexed by `TensorIndex`.
+    fn supports_node(&self, node: &NodeInfo, tensors: &[TensorView<'_>]) -> bool;
+
+    /// Creates the kernel running the nodes of `partition`.
+    fn create_kernel(
+        &self,
+        partition: &Partition,
+        tensors: &[TensorView<'_>],
+    ) -> Result<Box<dyn DelegateKernel>>;
+
+    /// Copies the data behind `buffer_handle` into `tensor`.
+    fn copy_from_buffer_handle(
+        &self,
+        _buffer_handle: BufferHandle,
+        _tensor: &mut TensorViewMut<'_>,
+    ) -> Result<()> {
+        Err(Error::Delegate("delegate does not support buffer handles".to_string()))
+    }
+
+    /// Releases the data behind `buffer_handle`.
+    fn free_buffer_handle(&self, _buffer_handle: BufferHandle) {}
+}
+
+/// Runs a partition of nodes claimed by a `Delegate`.
+pub trait DelegateKernel: Send {
+    /// Called whenever the tensors are (re)allocated, e.g. to validate shapes.
+    /// The data of the tensors may not be allocated yet.
+    fn prepare(
+        &mut self,
+        _inputs: &[TensorView<'_>],
+        _outputs: &mut [TensorViewMut<'_>],
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    fn invoke(
+        &mut self,
+        inputs: &[TensorView<'_>],
+        outputs: &mut [TensorViewMut<'_>],
+    ) -> Result<()>;
+}
+
+/// A connected set of nodes claimed by a `Delegate`.
+#[derive(Debug, Clone)]
+pub struct Partition {
+    pub nodes: Vec<NodeInfo>,
+    /// Tensors consumed by the partition, including constant tensors.
+    pub inputs: Vec<TensorIndex>,
+    pub outputs: Vec<TensorIndex>,
+}
+
+/// Adapts a `Delegate` to a `TfLiteDelegate`.
+pub struct CustomDelegate {
+    handle: *mut SysDelegate,
+    _delegate: Box<Box<dyn Delegate>>,
+}
+
+unsafe impl Send for CustomDelegate {}
+unsafe impl Sync for CustomDelegate {}
+
+impl Drop for CustomDelegate {
+    fn drop(&mut self) {
+        let handle = self.handle;
+
+        #[allow(clippy::forgetting_copy_types, clippy::useless_transmute, deprecated)]
+        unsafe {
+            cpp!([handle as "TfLiteDelegate*"] {
+                delete handle;
+            });
+        }
+    }
+}
+
+impl OwnedDelegate for CustomDelegate {
+    fn get_delegate_handle(&self) -> *mut SysDelegate {
+        self.handle
+    }
+}
+
+impl CustomDelegate {
+    pub fn new<D: Delegate>(delegate: D) -> Self {
+        let delegate: Box<Box<dyn Delegate>> = Box::new(Box::new(delegate));
+        let data = &*delegate as *const Box<dyn Delegate> as *mut c_void;
+
+        #[allow(clippy::forgetting_copy_types, deprecated)]
+        let handle = unsafe {
+            cpp!([data as "void*"] -> *mut SysDelegate as "TfLiteDelegate*" {
+                TfLiteDelegate* delegate = new TfLiteDelegate(TfLiteDelegateCreate());
+                delegate->data_ = data;
+                delegate->Prepare = CustomDelegatePrepare;
+                delegate->CopyFromBufferHandle = CustomDelegateCopyFromBufferHandle;
+                delegate->FreeBufferHandle = CustomDelegateFreeBufferHandle;
+                return delegate;
+            })
+        };
+        Self { handle, _delegate: delegate }
+    }
+}
+
+unsafe fn delegate_ref<'a>(delegate: *const SysDelegate) -> &'a dyn Delegate {
+    &**((*delegate).data_ as *const Box<dyn Delegate>)
+}
+
+unsafe fn execution_plan(context: *mut TfLiteContext) -> Result<Vec<c_int>> {
+    let mut plan: *mut TfLiteIntArray = ptr::null_mut();
+
+    #[allow(clippy::forgetting_copy_types, deprecated)]
+    let r = cpp!([context as "TfLiteContext*", mut plan as "TfLiteIntArray*"] -> bool as "bool" {
+        return context->GetExecutionPlan(context, &plan) == kTfLiteOk;
+    });
+    if !r {
+        return Err(Error::Delegate("failed to get execution plan".to_string()));
+    }
+    Ok(int_array(plan).to_vec())
+}
+
+unsafe fn context_node(context: *mut TfLiteContext, node_index: c_int) -> Result<NodeInfo> {
+    let mut node: *mut TfLiteNode = ptr::null_mut();
+    let mut registration: *mut TfLiteRegistration = ptr::null_mut();
+
+    #[allow(clippy::forgetting_copy_types, deprecated)]
+    let r = cpp!([
+        context as "TfLiteContext*",
+        node_index as "int",
+        mut node as "TfLiteNode*",
+        mut registration as "TfLiteRegistration*"
+    ] -> bool as "bool" {
+        return context->GetNodeAndRegistration(context, node_index, &node, &registration) == kTfLiteOk;
+    });
+    if !r {
+        return Err(Error::Delegate(format!("failed to get node {node_index}")));
+    }
+    Ok(NodeInfo::from_raw(node_index as usize, &*node, &*registration))
+}
+
+unsafe fn context_tensors<'a>(context: *mut TfLiteContext) -> Vec<TensorView<'a>> {
+    let context = &*context;
+    if context.tensors.is_null() {
+        return Vec::new();
+    }
+    slice::from_raw_parts(context.tensors, context.tensors_size)
+        .iter()
+        .enumerate()
+        .map(|(index, tensor)| TensorView::new(index as TensorIndex, tensor))
+        .collect()
+}
+
+unsafe fn prepare(context: *mut TfLiteContext, delegate: *mut SysDelegate) -> bool {
+    guarded(context, || {
+        let nodes = {
+            let tensors = context_tensors(context);
+            let mut nodes = Vec::new();
+            for node_index in execution_plan(context)? {
+                let node = context_node(context, node_index)?;
+                if delegate_ref(delegate).supports_node(&node, &tensors) {
+                    nodes.push(node_index);
+                }
+            }
+            nodes
+        };
+        if nodes.is_empty() {
+            return Ok(());
+        }
+
+        let nodes_ptr = nodes.as_ptr();
+        let nodes_len = nodes.len() as size_t;
+
+        #[allow(clippy::forgetting_copy_types, deprecated, clippy::transmute_num_to_bytes)]
+        let r = cpp!([
+            context as "TfLiteContext*",
+            delegate as "TfLiteDelegate*",
+            nodes_ptr as "const int*",
+            nodes_len as "size_t"
+        ] -> bool as "bool" {
+            TfLiteIntArray* nodes = TfLiteIntArrayCreate(nodes_len);
+            std::copy(nodes_ptr, nodes_ptr + nodes_len, nodes->data);
+            TfLiteStatus status = context->ReplaceNodeSubsetsWithDelegateKernels(
+                context, CustomDelegateKernel(), nodes, delegate);
+            TfLiteIntArrayFree(nodes);
+            return status == kTfLiteOk;
+        });
+        if r {
+            Ok(())
+        } else {
+            Err(Error::Delegate("failed to replace nodes with delegate kernels".to_string()))
+        }
+    })
+    .is_some()
+}
+
+unsafe fn init_kernel(
+    context: *mut TfLiteContext,
+    delegate: *const SysDelegate,
+    nodes: *const TfLiteIntArray,
+    inputs: *const TfLiteIntArray,
+    outputs: *const TfLiteIntArray,
+) -> *mut c_void {
+    guarded(context, || {
+        let partition = Partition {
+            nodes: int_array(nodes)
+                .iter()
+                .map(|&node_index| context_node(context, node_index))
+                .collect::<Result<_>>()?,
+            inputs: int_array(inputs).to_vec(),
+            outputs: int_array(outputs).to_vec(),
+        };
+        let tensors = context_tensors(context);
+        let kernel = delegate_ref(delegate).create_kernel(&partition, &tensors)?;
+        Ok(Box::into_raw(Box::new(kernel)) as *mut c_void)
+    })
+    .unwrap_or(ptr::null_mut())
+}
+
+unsafe fn free_kernel(buffer: *mut c_void) {
+    if !buffer.is_null() {
+        let kernel = Box::from_raw(buffer as *mut Box<dyn DelegateKernel>);
+        let _ = panic::catch_unwind(AssertUnwindSafe(|| drop(kernel)));
+    }
+}
+
+unsafe fn run_kernel(context: *mut TfLiteContext, node: *mut TfLiteNode, invoke: bool) -> bool {
+    guarded(context, || {
+        let node = &*node;
+        let kernel = (node.user_data as *mut Box<dyn DelegateKernel>)
+            .as_mut()
+            .ok_or_else(|| Error::Delegate("delegate kernel was not created".to_string()))?;
+
+        let (inputs, mut outputs) = node_tensors(context, node)?;
+
+        if invoke {
+            kernel.invoke(&inputs, &mut outputs)
+        } else {
+            kernel.prepare(&inputs, &mut outputs)
+        }
+    })
+    .is_some()
+}
+
+unsafe fn copy_from_buffer_handle(
+    context: *mut TfLiteContext,
+    delegate: *mut SysDelegate,
+    buffer_handle: BufferHandle,
+    tensor: *mut TfLiteTensor,
+) -> bool {
+    guarded(context, || {
+        let index = tensor.offset_from((*context).tensors) as TensorIndex;
+        let mut tensor = TensorViewMut::new(index, &mut *tensor);
+        delegate_ref(delegate).copy_from_buffer_handle(buffer_handle, &mut tensor)
+    })
+    .is_some()
+}
+
+unsafe fn free_buffer_handle(delegate: *mut SysDelegate, buffer_handle: BufferHandle) {
+    let _ = panic::catch_unwind(AssertUnwindSafe(|| {
+        delegate_ref(delegate).free_buffer_handle(buffer_handle)
+    }));
+}
@@ -0,0 +1,15 @@
+mod custom;
+mod xnnpack;
+
+use crate::bindings::TfLiteDelegate as SysDelegate;
+pub use custom::{BufferHandle, CustomDelegate, Delegate, DelegateKernel, Partition};
+pub use xnnpack::{XnnpackDelegate, XnnpackOptions};
+
+/// A `TfLiteDelegate` owned from Rust.
+///
+/// Delegates passed to `InterpreterBuilder::add_delegate` or
+/// `Interpreter::modify_graph_with_delegate` are kept alive, and dropped only after
+/// the interpreter they were applied to.
+pub trait OwnedDelegate: Send + Sync {
+    fn get_delegate_handle(&self) -> *mut SysDelegate;
+}
@@ -0,0 +1,137 @@
+use std::ffi::CString;
+use std::path::PathBuf;
+use std::ptr;
+
+use super::{OwnedDelegate, SysDelegate};
+use crate::{Error, Result};
+
+cpp! {{
+    #include "tensorflow/lite/delegates/xnnpack/xnnpack_delegate.h"
+}}
+
+/// Options of the XNNPACK delegate.
+///
+/// More options may be added, so set them with the methods of the same name, e.g.
+/// `XnnpackOptions::default().num_threads(4)`.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct XnnpackOptions {
+    /// Number of threads used by XNNPACK. Values below 2 run single-threaded.
+    pub num_threads: i32,
+    /// Delegates signed and unsigned 8-bit quantized operators.
+    pub quantized: bool,
+    /// Runs float32 operators in float16 precision, trading accuracy for speed.
+    pub force_fp16: bool,
+    /// File caching the packed weights, so that they are shared across interpreters
+    /// and processes running the same model instead of being repacked.
+    pub weight_cache_file: Option<PathBuf>,
+}
+
+impl Default for XnnpackOptions {
+    fn default() -> Self {
+        Self { num_threads: 1, quantized: true, force_fp16: false, weight_cache_file: None }
+    }
+}
+
+impl XnnpackOptions {
+    pub fn num_threads(mut self, num_threads: i32) -> Self {
+        self.num_threads = num_threads;
+        self
+    }
+
+    pub fn quantized(mut self, quantized: bool) -> Self {
+        self.quantized = quantized;
+        self
+    }
+
+    pub fn force_fp16(mut self, force_fp16: bool) -> Self {
+        self.force_fp16 = force_fp16;
+        self
+    }
+
+    pub fn weight_cache_file(mut self, path: Option<PathBuf>) -> Self {
+        self.weight_cache_file = path;
+        self
+    }
+}
+
+/// The XNNPACK delegate, which runs supported operators with XNNPACK's CPU kernels.
+///
+/// `BuiltinOpResolver::default()` already applies XNNPACK with default options.
+/// To configure it, add this delegate with `InterpreterBuilder::add_delegate`;
+/// to run the builtin kernels only, use `BuiltinOpResolver::without_default_delegates()`.
+pub struct XnnpackDelegate {
+    handle: *mut SysDelegate,
+    _weight_cache_file: Option<CString>,
+}
+
+unsafe impl Send for XnnpackDelegate {}
+unsafe impl Sync for XnnpackDelegate {}
+
+impl Drop for XnnpackDelegate {
+    fn drop(&mut self) {
+        let handle = self.handle;
+
+        #[allow(clippy::forgetting_copy_types, clippy::useless_transmute, deprecated)]
+        unsafe {
+            cpp!([handle as "TfLiteDelegate*"] {
+                TfLiteXNNPackDelegateDelete(handle);
+            });
+        }
+    }
+}
+
+impl OwnedDelegate for XnnpackDelegate {
+    fn get_delegate_handle(&self) -> *mut SysDelegate {
+        self.handle
+    }
+}
+
+impl XnnpackDelegate {
+    pub fn new(options: &XnnpackOptions) -> Result<Self> {
+        let weight_cache_file = options
+            .weight_cache_file
+            .as_ref()
+            .map(|path| {
+                CString::new(path.to_string_lossy().into_owned()).map_err(|_| {
+                    Error::Delegate("weight cache path contains a nul byte".to_string())
+                })
+            })
+            .transpose()?;
+        let weight_cache_file_ptr =
+            weight_cache_file.as_ref().map_or(ptr::null(), |path| path.as_ptr());
+
+        let num_threads = options.num_threads;
+        let quantized = options.quantized;
+        let force_fp16 = options.force_fp16;
+
+        #[allow(clippy::forgetting_copy_types, deprecated, clippy::transmute_num_to_bytes)]
+        let handle = unsafe {
+            cpp!([
+                num_threads as "int32_t",
+                quantized as "bool",
+                force_fp16 as "bool",
+                weight_cache_file_ptr as "const char*"
+            ] -> *mut SysDelegate as "TfLiteDelegate*" {
+                TfLiteXNNPackDelegateOptions options = TfLiteXNNPackDelegateOptionsDefault();
+                options.num_threads = num_threads;
+                const uint32_t quantized_flags =
+                    TFLITE_XNNPACK_DELEGATE_FLAG_QS8 | TFLITE_XNNPACK_DELEGATE_FLAG_QU8;
+                if (quantized) {
+                    options.flags |= quantized_flags;
+                } else {
+                    options.flags &= ~quantized_flags;
+                }
+                if (force_fp16) {
+                    options.flags |= TFLITE_XNNPACK_DELEGATE_FLAG_FORCE_FP16;
+                }
+                options.weight_cache_file_path = weight_cache_file_ptr;
+                return TfLiteXNNPackDelegateCreate(&options);
+            })
+        };
+        if handle.is_null() {
+            return Err(Error::Delegate("failed to create XNNPACK delegate".to_string()));
+        }
+        Ok(Self { handle, _weight_cache_file: weight_cache_file })
+    }
+}
@@ -0,0 +1,140 @@
+use std::ffi::c_void;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::{mem, slice};
+
+use crate::bindings::TfLiteStatus;
+use crate::Error;
+
+cpp! {{
+    #include <cstdarg>
+    #include <cstdio>
+    #include <vector>
+
+    #include "tensorflow/lite/core/api/error_reporter.h"
+
+    class RustErrorReporter : public tflite::ErrorReporter {
+     public:
+        explicit RustErrorReporter(void* messages) : messages_(messages) {}
+
+        int Report(const char* format, va_list args) override {
+            va_list args_copy;
+            va_copy(args_copy, args);
+            int size = vsnprintf(nullptr, 0, format, args_copy);
+            va_end(args_copy);
+            if (size < 0) {
+                return size;
+            }
+            std::vector<char> buffer(size + 1);
+            vsnprintf(buffer.data(), buffer.size(), format, args);
+
+            void* messages = messages_;
+            const char* message = buffer.data();
+            size_t length = size;
+            rust!(RustErrorReporter_report [
+                messages: *const Mutex<Vec<String>> as "void*",
+                message: *const u8 as "const char*",
+                length: usize as "size_t"
+            ] {
+                let message = unsafe { slice::from_raw_parts(message, length) };
+                let message = String::from_utf8_lossy(message).trim().to_string();
+                if !message.is_empty() {
+                    if let Ok(mut messages) = unsafe { &*messages }.lock() {
+                        messages.push(message);
+                    }
+                }
+            });
+            return size;
+        }
+
+     private:
+        void* messages_;
+    };
+}}
+
+/// A `tflite::ErrorReporter` collecting the reported messages, so that they can be
+/// attached to the errors of the call that reported them.
+pub(crate) struct ErrorReporter {
+    handle: *mut c_void,
+    messages: Box<Mutex<Vec<String>>>,
+    /// Whether an invocation of the interpreter failed, see `invoke_error`.
+    invoke_failed: AtomicBool,
+}
+
+unsafe impl Send for ErrorReporter {}
+unsafe impl Sync for ErrorReporter {}
+
+impl Default for ErrorReporter {
+    fn default() -> Self {
+        let messages: Box<Mutex<Vec<String>>> = Box::default();
+        let messages_ptr = &*messages as *const Mutex<Vec<String>> as *mut c_void;
+
+        #[allow(clippy::forgetting_copy_types, deprecated)]
+        let handle = unsafe {
+            cpp!([messages_ptr as "void*"] -> *mut c_void as "tflite::ErrorReporter*" {
+                return new RustErrorReporter(messages_ptr);
+            })
+        };
+        Self { handle, messages, invoke_failed: AtomicBool::new(false) }
+    }
+}
+
+impl Drop for ErrorReporter {
+    fn drop(&mut self) {
+        let handle = self.handle;
+
+        #[allow(clippy::forgetting_copy_types, clippy::useless_transmute, deprecated)]
+        unsafe {
+            cpp!([handle as "tflite::ErrorReporter*"] {
+                delete handle;
+            });
+        }
+    }
+}
+
+impl ErrorReporter {
+    /// The `tflite::ErrorReporter*` to hand to TFLite.
+    pub(crate) fn handle(&self) -> *mut c_void {
+        self.handle
+    }
+
+    /// Discards the messages reported so far.
+    pub(crate) fn clear(&self) {
+        self.messages.lock().unwrap().clear();
+    }
+
+    /// Creates an error of `kind` from `summary` followed by the messages reported
+    /// since the last error or `clear`.
+    pub(crate) fn error(&self, kind: fn(String) -> Error, summary: &str) -> Error {
+        let messages = mem::take(&mut *self.messages.lock().unwrap());
+        if messages.is_empty() {
+            kind(summary.to_string())
+        } else {
+            kind(format!("{summary}: {}", messages.join("; ")))
+        }
+    }
+
+    /// Creates an `Error::Invoke` like `error`, and records that an invocation failed.
+    pub(crate) fn invoke_error(&self, summary: &str) -> Error {
+        self.invoke_failed.store(true, Ordering::SeqCst);
+        self.error(Error::Invoke, summary)
+    }
+
+    /// Returns whether an invocation failed, other than by cancellation.
+    pub(crate) fn invoke_failed(&self) -> bool {
+        self.invoke_failed.load(Ordering::SeqCst)
+    }
+
+    /// Like `error`, but reports a `kTfLiteUnresolvedOps` status, returned when a node
+    /// has no kernel, as `Error::UnsupportedOp`.
+    pub(crate) fn status_error(
+        &self,
+        status: TfLiteStatus,
+        kind: fn(String) -> Error,
+        summary: &str,
+    ) -> Error {
+        let unsupported = status == TfLiteStatus::kTfLiteUnresolvedOps;
+        let kind: fn(String) -> Error = if unsupported { Error::UnsupportedOp } else { kind };
+        self.error(kind, summary)
+    }
+}
@@ -1,6 +1,13 @@
+use std::ffi::CString;
+use std::ops::Deref;
 use std::path::Path;
-use std::{fs, mem};
+use std::sync::Arc;
+use std::{fs, io, mem, slice};
 
+use libc::size_t;
+use maybe_owned::MaybeOwned;
+
+use super::error_reporter::ErrorReporter;
 use crate::bindings::tflite as bindings;
 use crate::model::Model;
 use crate::{Error, Result};
@@ -8,6 +15,7 @@ use crate::{Error, Result};
 cpp! {{
     #include "tensorflow/lite/model.h"
     #include "tensorflow/lite/kernels/register.h"
+    #include "tensorflow/lite/schema/schema_generated.h"
 
     using namespace tflite;
 }}
@@ -15,7 +23,20 @@ cpp! {{
 #[derive(Default)]
 pub struct FlatBufferModel {
     pub(crate) handle: Box<bindings::FlatBufferModel>,
-    model_buffer: Vec<u8>,
+    model_buffer: ModelBuffer,
+    error_reporter: ErrorReporter,
+}
+
+/// The memory a `FlatBufferModel` was built from.
+#[derive(Default)]
+enum ModelBuffer {
+    #[default]
+    Empty,
+    Owned(Vec<u8>),
+    Static(&'static [u8]),
+    /// The file is mapped, or read if mapping is not supported, by TensorFlow Lite's
+    /// `Allocation` of the model.
+    Mapped,
 }
 
 impl Drop for FlatBufferModel {
@@ -36,33 +57,212 @@ impl FlatBufferModel {
         Self::build_from_buffer(fs::read(path)?)
     }
 
+    /// Builds a model from a memory-mapped file, so that the model is paged in on demand
+    /// and shared with other processes mapping the same file instead of being copied.
+    ///
+    /// The file must not be modified while the model is alive.
+    pub fn build_from_mmap<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        // Report a missing or unreadable file as such rather than as an invalid model.
+        fs::File::open(path)?;
+        let path = path.to_str().and_then(|path| CString::new(path).ok()).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "model path is not valid UTF-8 without nul")
+        })?;
+        let path_ptr = path.as_ptr();
+        let error_reporter = ErrorReporter::default();
+        let reporter = error_reporter.handle();
+
+        #[allow(clippy::forgetting_copy_types, deprecated)]
+        let handle = unsafe {
+            cpp!([path_ptr as "const char*", reporter as "ErrorReporter*"]
+                  -> *mut bindings::FlatBufferModel as "FlatBufferModel*" {
+                return FlatBufferModel::VerifyAndBuildFromFile(path_ptr, nullptr, reporter)
+                    .release();
+            })
+        };
+        Self::from_handle(handle, ModelBuffer::Mapped, error_reporter)
+    }
+
+    /// Builds a model from `model_buffer` after verifying that it is a valid flatbuffer.
+    /// The messages of a failed verification are part of the `Error::ModelVerification`.
     pub fn build_from_buffer(model_buffer: Vec<u8>) -> Result<Self> {
-        let ptr = model_buffer.as_ptr();
-        let size = model_buffer.len();
+        let (ptr, size) = (model_buffer.as_ptr(), model_buffer.len());
+        Self::build_from_memory(ptr, size, ModelBuffer::Owned(model_buffer))
+    }
+
+    /// Builds a model from a buffer that lives for the whole program, e.g. one embedded
+    /// with `include_bytes!`, without copying it.
+    ///
+    /// The buffer must be aligned to at least 4 bytes, which `include_bytes!` does not
+    /// guarantee on its own, or `Error::ModelVerification` is returned.
+    pub fn build_from_static(model_buffer: &'static [u8]) -> Result<Self> {
+        if model_buffer.as_ptr() as usize % 4 != 0 {
+            return Err(Error::ModelVerification(
+                "static model buffer must be aligned to 4 bytes".to_string(),
+            ));
+        }
+        let (ptr, size) = (model_buffer.as_ptr(), model_buffer.len());
+        Self::build_from_memory(ptr, size, ModelBuffer::Static(model_buffer))
+    }
+
+    /// Builds a model from the `size` bytes at `ptr`, which must be owned by `buffer`.
+    fn build_from_memory(ptr: *const u8, size: usize, buffer: ModelBuffer) -> Result<Self> {
+        let error_reporter = ErrorReporter::default();
+        let reporter = error_reporter.handle();
 
         #[allow(clippy::forgetting_copy_types, deprecated, clippy::transmute_num_to_bytes)]
         let handle = unsafe {
-            cpp!([ptr as "const char*", size as "size_t"]
+            cpp!([ptr as "const char*", size as "size_t", reporter as "ErrorReporter*"]
                   -> *mut bindings::FlatBufferModel as "FlatBufferModel*" {
-                return FlatBufferModel::BuildFromBuffer(ptr, size).release();
+                return FlatBufferModel::VerifyAndBuildFromBuffer(ptr, size, nullptr, reporter)
+                    .release();
             })
         };
+        Self::from_handle(handle, buffer, error_reporter)
+    }
+
+    fn from_handle(
+        handle: *mut bindings::FlatBufferModel,
+        model_buffer: ModelBuffer,
+        error_reporter: ErrorReporter,
+    ) -> Result<Self> {
         if handle.is_null() {
-            return Err(Error::internal_error("failed to build model"));
+            return Err(error_reporter.error(Error::ModelVerification, "failed to build model"));
         }
         let handle = unsafe { Box::from_raw(handle) };
-        Ok(Self { handle, model_buffer })
+        Ok(Self { handle, model_buffer, error_reporter })
     }
 
     pub fn build_from_model(model: &Model) -> Result<Self> {
         FlatBufferModel::build_from_buffer(model.to_buffer())
     }
 
+    /// Returns the flatbuffer of the model, which for a memory-mapped model is the mapping.
     pub fn buffer(&self) -> &[u8] {
-        &self.model_buffer
+        match &self.model_buffer {
+            ModelBuffer::Empty => &[],
+            ModelBuffer::Owned(buffer) => buffer,
+            ModelBuffer::Static(buffer) => buffer,
+            ModelBuffer::Mapped => {
+                let handle = &*self.handle;
+                let mut size: size_t = 0;
+
+                #[allow(clippy::forgetting_copy_types, deprecated, clippy::transmute_num_to_bytes)]
+                let ptr = unsafe {
+                    cpp!([handle as "const FlatBufferModel*", mut size as "size_t"]
+                          -> *const u8 as "const void*" {
+                        const Allocation* allocation = handle->allocation();
+                        size = allocation->bytes();
+                        return allocation->base();
+                    })
+                };
+                unsafe { slice::from_raw_parts(ptr, size) }
+            }
+        }
+    }
+
+    /// Returns the contents of the metadata entry `name` of the model, e.g. `TFLITE_METADATA`
+    /// written by the TensorFlow Lite Support library, or `None` if there is none.
+    pub fn metadata(&self, name: &str) -> Option<&[u8]> {
+        let handle = &*self.handle;
+        let (name_ptr, name_len) = (name.as_ptr(), name.len());
+        let mut size: size_t = 0;
+
+        #[allow(clippy::forgetting_copy_types, deprecated, clippy::transmute_num_to_bytes)]
+        let ptr = unsafe {
+            cpp!([
+                handle as "const FlatBufferModel*",
+                name_ptr as "const char*",
+                name_len as "size_t",
+                mut size as "size_t"
+            ] -> *const u8 as "const uint8_t*" {
+                const Model* model = handle->GetModel();
+                if (model == nullptr || model->metadata() == nullptr || model->buffers() == nullptr) {
+                    return nullptr;
+                }
+                const std::string wanted(name_ptr, name_len);
+                for (const auto* metadata : *model->metadata()) {
+                    if (metadata->name() == nullptr || metadata->name()->str() != wanted) {
+                        continue;
+                    }
+                    if (metadata->buffer() >= model->buffers()->size()) {
+                        return nullptr;
+                    }
+                    const auto* data = model->buffers()->Get(metadata->buffer())->data();
+                    if (data == nullptr) {
+                        return nullptr;
+                    }
+                    size = data->size();
+                    return data->data();
+                }
+                return nullptr;
+            })
+        };
+        if ptr.is_null() {
+            None
+        } else {
+            Some(unsafe { slice::from_raw_parts(ptr, size) })
+        }
     }
 
+    /// Returns the flatbuffer of the model, copying it unless the model was built from
+    /// an owned buffer.
     pub fn release_buffer(mut self) -> Vec<u8> {
-        mem::take(&mut self.model_buffer)
+        match mem::take(&mut self.model_buffer) {
+            ModelBuffer::Owned(buffer) => buffer,
+            buffer => {
+                self.model_buffer = buffer;
+                self.buffer().to_vec()
+            }
+        }
+    }
+}
+
+/// A model owned, borrowed or shared by an `InterpreterBuilder` and its `Interpreter`.
+///
+/// Owned and shared models make `'static` interpreters, which can be stored and moved
+/// freely; an `Arc<FlatBufferModel>` lets several of them use the same model.
+pub enum ModelRef<'a> {
+    Borrowed(&'a FlatBufferModel),
+    Owned(FlatBufferModel),
+    Shared(Arc<FlatBufferModel>),
+}
+
+impl Deref for ModelRef<'_> {
+    type Target = FlatBufferModel;
+
+    fn deref(&self) -> &Self::Target {
+        match self {
+            ModelRef::Borrowed(model) => model,
+            ModelRef::Owned(model) => model,
+            ModelRef::Shared(model) => model,
+        }
+    }
+}
+
+impl<'a> From<&'a FlatBufferModel> for ModelRef<'a> {
+    fn from(model: &'a FlatBufferModel) -> Self {
+        ModelRef::Borrowed(model)
+    }
+}
+
+impl From<FlatBufferModel> for ModelRef<'_> {
+    fn from(model: FlatBufferModel) -> Self {
+        ModelRef::Owned(model)
+    }
+}
+
+impl From<Arc<FlatBufferModel>> for ModelRef<'_> {
+    fn from(model: Arc<FlatBufferModel>) -> Self {
+        ModelRef::Shared(model)
+    }
+}
+
+impl<'a> From<MaybeOwned<'a, FlatBufferModel>> for ModelRef<'a> {
+    fn from(model: MaybeOwned<'a, FlatBufferModel>) -> Self {
+        match model {
+            MaybeOwned::Borrowed(model) => ModelRef::Borrowed(model),
+            MaybeOwned::Owned(model) => ModelRef::Owned(model),
+        }
     }
 }
@@ -0,0 +1,220 @@
+//! A reader of the parts of the TensorFlow Lite model metadata, the `ModelMetadata`
+//! flatbuffer of `metadata_schema.fbs`, that preprocessing uses.
+
+use crate::{Error, Result};
+
+/// The name of the model metadata written by the TensorFlow Lite Support library.
+pub(crate) const METADATA_NAME: &str = "TFLITE_METADATA";
+
+const FILE_IDENTIFIER: &[u8; 4] = b"M001";
+
+// Field ids of the tables, in their order in `metadata_schema.fbs`.
+const MODEL_SUBGRAPH_METADATA: usize = 3;
+const SUBGRAPH_INPUT_TENSOR_METADATA: usize = 2;
+const TENSOR_PROCESS_UNITS: usize = 4;
+const PROCESS_UNIT_OPTIONS_TYPE: usize = 0;
+const PROCESS_UNIT_OPTIONS: usize = 1;
+const NORMALIZATION_MEAN: usize = 0;
+const NORMALIZATION_STD: usize = 1;
+
+/// The `ProcessUnitOptions` union tag of `NormalizationOptions`.
+const NORMALIZATION_OPTIONS: u8 = 1;
+
+/// The mean and std of the `NormalizationOptions` of the `input`th input of the first
+/// subgraph, or `None` if the metadata has none.
+pub(crate) fn input_normalization(
+    metadata: &[u8],
+    input: usize,
+) -> Result<Option<(Vec<f32>, Vec<f32>)>> {
+    if metadata.get(4..8) != Some(&FILE_IDENTIFIER[..]) {
+        return Err(malformed());
+    }
+    let model = Table::at(metadata, read_u32(metadata, 0)?)?;
+    let Some(subgraph) = model.tables(MODEL_SUBGRAPH_METADATA)?.into_iter().next() else {
+        return Ok(None);
+    };
+    let Some(tensor) = subgraph.tables(SUBGRAPH_INPUT_TENSOR_METADATA)?.into_iter().nth(input)
+    else {
+        return Ok(None);
+    };
+    for unit in tensor.tables(TENSOR_PROCESS_UNITS)? {
+        if unit.byte(PROCESS_UNIT_OPTIONS_TYPE)? == NORMALIZATION_OPTIONS {
+            let options = unit.table(PROCESS_UNIT_OPTIONS)?.ok_or_else(malformed)?;
+            return Ok(Some((
+                options.floats(NORMALIZATION_MEAN)?,
+                options.floats(NORMALIZATION_STD)?,
+            )));
+        }
+    }
+    Ok(None)
+}
+
+/// A flatbuffer table, whose fields are read with bounds checks.
+struct Table<'a> {
+    buffer: &'a [u8],
+    position: usize,
+    vtable: usize,
+}
+
+impl<'a> Table<'a> {
+    fn at(buffer: &'a [u8], position: usize) -> Result<Self> {
+        let offset = i32::from_le_bytes(read(buffer, position)?);
+        let vtable = (position as i64 - offset as i64).try_into().map_err(|_| malformed())?;
+        Ok(Self { buffer, position, vtable })
+    }
+
+    /// Returns the position of the field `id`, or `None` if it is not set.
+    fn field(&self, id: usize) -> Result<Option<usize>> {
+        let vtable_size = u16::from_le_bytes(read(self.buffer, self.vtable)?) as usize;
+        let entry = 4 + 2 * id;
+        if entry + 2 > vtable_size {
+            return Ok(None);
+        }
+        match u16::from_le_bytes(read(self.buffer, self.vtable + entry)?) {
+            0 => Ok(None),
+            offset => Ok(Some(self.position + offset as usize)),
+        }
+    }
+
+    /// Returns the position of the object the offset field `id` refers to.
+    fn object(&self, id: usize) -> Result<Option<usize>> {
+        let Some(field) = self.field(id)? else {
+            return Ok(None);
+        };
+        let position = field.checked_add(read_u32(self.buffer, field)?).ok_or_else(malformed)?;
+        Ok(Some(position))
+    }
+
+    fn byte(&self, id: usize) -> Result<u8> {
+        match self.field(id)? {
+            Some(field) => Ok(read::<1>(self.buffer, field)?[0]),
+            None => Ok(0),
+        }
+    }
+
+    fn table(&self, id: usize) -> Result<Option<Table<'a>>> {
+        self.object(id)?.map(|position| Table::at(self.buffer, position)).transpose()
+    }
+
+    fn tables(&self, id: usize) -> Result<Vec<Table<'a>>> {
+        self.vector(id, |buffer, element| {
+            Table::at(
+                buffer,
+                element.checked_add(read_u32(buffer, element)?).ok_or_else(malformed)?,
+            )
+        })
+    }
+
+    fn floats(&self, id: usize) -> Result<Vec<f32>> {
+        self.vector(id, |buffer, element| Ok(f32::from_le_bytes(read(buffer, element)?)))
+    }
+
+    /// Reads the elements of the vector field `id`, of 4 bytes each, with `element`.
+    fn vector<T>(
+        &self,
+        id: usize,
+        element: impl Fn(&'a [u8], usize) -> Result<T>,
+    ) -> Result<Vec<T>> {
+        let Some(vector) = self.object(id)? else {
+            return Ok(Vec::new());
+        };
+        let len = read_u32(self.buffer, vector)?;
+        if len > self.buffer.len() / 4 {
+            return Err(malformed());
+        }
+        (0..len).map(|i| element(self.buffer, vector + 4 + 4 * i)).collect()
+    }
+}
+
+fn read<const N: usize>(buffer: &[u8], position: usize) -> Result<[u8; N]> {
+    let end = position.checked_add(N).ok_or_else(malformed)?;
+    let bytes = buffer.get(position..end).ok_or_else(malformed)?;
+    Ok(bytes.try_into().unwrap())
+}
+
+fn read_u32(buffer: &[u8], position: usize) -> Result<usize> {
+    Ok(u32::from_le_bytes(read(buffer, position)?) as usize)
+}
+
+fn malformed() -> Error {
+    Error::ModelVerification("malformed model metadata".to_string())
+}
+
+/// Builds metadata whose first input has the `NormalizationOptions` `mean` and `std`.
+#[cfg(test)]
+pub(crate) fn normalization_metadata(mean: &[f32], std: &[f32]) -> Vec<u8> {
+    struct Writer(Vec<u8>);
+
+    impl Writer {
+        /// Points the offset at `from` to the end of the buffer.
+        fn point(&mut self, from: usize) {
+            let offset = (self.0.len() - from) as u32;
+            self.0[from..from + 4].copy_from_slice(&offset.to_le_bytes());
+        }
+
+        /// Appends a table of `slots` fields of 4 bytes, referred to by the offset at `from`,
+        /// setting the `u8` fields `bytes`, and returns the positions of the fields.
+        fn table(&mut self, from: usize, slots: usize, bytes: &[(usize, u8)]) -> Vec<usize> {
+            let vtable = self.0.len();
+            self.0.extend_from_slice(&((4 + 2 * slots) as u16).to_le_bytes());
+            self.0.extend_from_slice(&((4 + 4 * slots) as u16).to_le_bytes());
+            for slot in 0..slots {
+                self.0.extend_from_slice(&((4 + 4 * slot) as u16).to_le_bytes());
+            }
+            self.point(from);
+            let table = self.0.len();
+            self.0.extend_from_slice(&((table - vtable) as i32).to_le_bytes());
+            self.0.resize(table + 4 + 4 * slots, 0);
+            for &(slot, byte) in bytes {
+                self.0[table + 4 + 4 * slot] = byte;
+            }
+            (0..slots).map(|slot| table + 4 + 4 * slot).collect()
+        }
+
+        /// Appends a vector of one offset, referred to by the offset at `from`, and returns
+        /// the position of the offset.
+        fn vector_of_one(&mut self, from: usize) -> usize {
+            self.point(from);
+            self.0.extend_from_slice(&1u32.to_le_bytes());
+            self.0.extend_from_slice(&[0; 4]);
+            self.0.len() - 4
+        }
+
+        fn floats(&mut self, from: usize, values: &[f32]) {
+            self.point(from);
+            self.0.extend_from_slice(&(values.len() as u32).to_le_bytes());
+            for value in values {
+                self.0.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+    }
+
+    let mut writer = Writer([[0; 4], *FILE_IDENTIFIER].concat());
+    let model = writer.table(0, 4, &[]);
+    let subgraph = writer.vector_of_one(model[MODEL_SUBGRAPH_METADATA]);
+    let subgraph = writer.table(subgraph, 3, &[]);
+    let tensor = writer.vector_of_one(subgraph[SUBGRAPH_INPUT_TENSOR_METADATA]);
+    let tensor = writer.table(tensor, 5, &[]);
+    let unit = writer.vector_of_one(tensor[TENSOR_PROCESS_UNITS]);
+    let unit = writer.table(unit, 2, &[(PROCESS_UNIT_OPTIONS_TYPE, NORMALIZATION_OPTIONS)]);
+    let options = writer.table(unit[PROCESS_UNIT_OPTIONS], 2, &[]);
+    writer.floats(options[NORMALIZATION_MEAN], mean);
+    writer.floats(options[NORMALIZATION_STD], std);
+    writer.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalization() {
+        let metadata = normalization_metadata(&[127.5], &[127.5, 64.0, 1.0]);
+        let (mean, std) = input_normalization(&metadata, 0).unwrap().unwrap();
+        assert_eq!((mean, std), (vec![127.5], vec![127.5, 64.0, 1.0]));
+        assert!(input_normalization(&metadata, 1).unwrap().is_none());
+
+        assert!(input_normalization(&metadata[..metadata.len() - 1], 0).is_err());
+        assert!(matches!(input_normalization(b"0000TFL3", 0), Err(Error::ModelVerification(_))));
+    }
+}
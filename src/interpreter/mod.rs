@@ -1,35 +1,83 @@
+mod allocation;
+#[cfg(feature = "ndarray")]
+mod array;
 mod builder;
+mod cancellation;
 pub mod context;
+pub mod delegate;
+mod error_reporter;
 mod fbmodel;
+#[cfg(feature = "image")]
+mod metadata;
+mod node;
 pub mod op_resolver;
 pub mod ops;
+mod pool;
+#[cfg(feature = "image")]
+pub mod preprocessing;
+pub mod profiler;
+mod signature;
+mod state;
+mod subgraph;
+mod tensor;
 
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::{c_void, CStr, CString};
 use std::mem;
+use std::ptr;
 use std::slice;
+use std::time::{Duration, Instant};
 
 use libc::{c_int, size_t};
 
 use crate::{bindings, Error, Result};
-pub use builder::InterpreterBuilder;
-use context::{ElemKindOf, ElementKind, QuantizationParams, TensorInfo};
-pub use fbmodel::FlatBufferModel;
+pub use allocation::{AlignedBuffer, TENSOR_ALIGNMENT};
+pub use builder::{InterpreterBuilder, InterpreterOptions};
+pub use cancellation::CancellationToken;
+use cancellation::{Cancellation, Deadline};
+use context::{
+    check_elem_kind_of, ElemKindOf, ElementKind, QuantizationParams, QuantizedElem, TensorInfo,
+};
+use delegate::OwnedDelegate;
+use error_reporter::ErrorReporter;
+pub use fbmodel::{FlatBufferModel, ModelRef};
+use node::delegate_kernel_of;
+pub use node::NodeInfo;
 use op_resolver::OpResolver;
+pub use pool::{InterpreterPool, InterpreterPoolOptions, PooledInterpreter};
+use profiler::{Profiler, ProfilerHandle};
+pub use signature::SignatureRunner;
+pub use state::InterpreterState;
+pub use subgraph::{MemoryInfo, Subgraph};
+use tensor::check_len;
+pub use tensor::{InputTensor, OutputTensor, OwnedTensor, TensorValue};
 
 cpp! {{
     #include "tensorflow/lite/interpreter.h"
     #include "tensorflow/lite/optional_debug_tools.h"
+    #include "tensorflow/lite/string_util.h"
 
     using namespace tflite;
 }}
 
 pub type TensorIndex = c_int;
 
+/// An interpreter that owns or shares its model and boxes its op resolver, so that it
+/// can be stored and moved without lifetime or resolver type parameters.
+pub type DynInterpreter = Interpreter<'static, Box<dyn OpResolver>>;
+
+pub type DynInterpreterBuilder = InterpreterBuilder<'static, Box<dyn OpResolver>>;
+
 pub struct Interpreter<'a, Op>
 where
     Op: OpResolver,
 {
     handle: Box<bindings::tflite::Interpreter>,
-    _builder: InterpreterBuilder<'a, Op>,
+    builder: InterpreterBuilder<'a, Op>,
+    delegates: Vec<Box<dyn OwnedDelegate>>,
+    profiler: Option<ProfilerHandle>,
+    cancellation: Option<Box<Cancellation>>,
+    allocations: Vec<AlignedBuffer>,
 }
 
 impl<'a, Op> Drop for Interpreter<'a, Op>
@@ -59,15 +107,30 @@ where
         use std::ops::DerefMut;
         self.handle.deref_mut()
     }
+    fn error_reporter(&self) -> &ErrorReporter {
+        self.builder.error_reporter()
+    }
+    /// Whether an invocation of the interpreter, a subgraph or a signature failed,
+    /// other than by cancellation, which may have left it in an inconsistent state.
+    pub(crate) fn invoke_failed(&self) -> bool {
+        self.error_reporter().invoke_failed()
+    }
     pub(crate) fn new(
         handle: *mut bindings::tflite::Interpreter,
         builder: InterpreterBuilder<'a, Op>,
     ) -> Result<Self> {
         if handle.is_null() {
-            return Err(Error::internal_error("failed to create interpreter"));
+            return Err(Error::Allocation("failed to create interpreter".to_string()));
         }
         let handle = unsafe { Box::from_raw(handle) };
-        let mut interpreter = Self { handle, _builder: builder };
+        let mut interpreter = Self {
+            handle,
+            builder,
+            delegates: Vec::new(),
+            profiler: None,
+            cancellation: None,
+            allocations: Vec::new(),
+        };
         // # Safety
         // Always allocate tensors so we don't get into a state
         // where we try to read from or write to unallocated memory
@@ -80,28 +143,136 @@ where
     /// the input tensor dimensionality as given. This is relatively expensive.
     /// If you know that your sizes are not changing, you need not call this.
     pub fn allocate_tensors(&mut self) -> Result<()> {
+        self.error_reporter().clear();
         let interpreter = self.handle_mut();
 
         #[allow(clippy::forgetting_copy_types, deprecated)]
+        let status = unsafe {
+            cpp!([interpreter as "Interpreter*"] -> bindings::TfLiteStatus as "TfLiteStatus" {
+                return interpreter->AllocateTensors();
+            })
+        };
+        if status == bindings::TfLiteStatus::kTfLiteOk {
+            Ok(())
+        } else {
+            Err(self.error_reporter().status_error(
+                status,
+                Error::Allocation,
+                "failed to allocate tensors",
+            ))
+        }
+    }
+
+    /// Changes the dimensionality of the input tensor `tensor_index`.
+    ///
+    /// Tensors must be reallocated with `allocate_tensors` before they are accessed again.
+    /// Afterwards `tensor_info` and `get_output_details` report the propagated dimensions.
+    pub fn resize_input_tensor(&mut self, tensor_index: TensorIndex, dims: &[usize]) -> Result<()> {
+        self.resize_input_tensor_impl(tensor_index, dims, false)
+    }
+
+    /// Like `resize_input_tensor`, but only the dimensions that are unknown (`-1`)
+    /// in the tensor's `shape_signature` may change.
+    pub fn resize_input_tensor_strict(
+        &mut self,
+        tensor_index: TensorIndex,
+        dims: &[usize],
+    ) -> Result<()> {
+        self.resize_input_tensor_impl(tensor_index, dims, true)
+    }
+
+    fn resize_input_tensor_impl(
+        &mut self,
+        tensor_index: TensorIndex,
+        dims: &[usize],
+        strict: bool,
+    ) -> Result<()> {
+        if self.tensor_inner(tensor_index).is_none() {
+            return Err(invalid_tensor_index(tensor_index));
+        }
+        self.error_reporter().clear();
+        let interpreter = self.handle_mut();
+
+        let dims: Vec<i32> = dims.iter().map(|x| *x as i32).collect();
+        let dims_ptr = dims.as_ptr();
+        let dims_len = dims.len() as size_t;
+
+        #[allow(clippy::forgetting_copy_types, deprecated, clippy::transmute_num_to_bytes)]
         let r = unsafe {
-            cpp!([interpreter as "Interpreter*"] -> bool as "bool" {
-                return interpreter->AllocateTensors() == kTfLiteOk;
+            cpp!([
+                interpreter as "Interpreter*",
+                tensor_index as "int",
+                dims_ptr as "const int*",
+                dims_len as "size_t",
+                strict as "bool"
+            ] -> bool as "bool" {
+                std::vector<int> dims(dims_ptr, dims_ptr + dims_len);
+                if (strict) {
+                    return interpreter->ResizeInputTensorStrict(tensor_index, dims) == kTfLiteOk;
+                }
+                return interpreter->ResizeInputTensor(tensor_index, dims) == kTfLiteOk;
             })
         };
         if r {
             Ok(())
         } else {
-            Err(Error::internal_error("failed to allocate tensors"))
+            Err(self.error_reporter().error(Error::Allocation, "failed to resize input tensor"))
+        }
+    }
+
+    /// Binds `buffer` as the backing store of the input or output tensor `tensor_index`,
+    /// so that its data is read from or written to `buffer` without a copy, and reallocates
+    /// the tensors.
+    ///
+    /// `buffer` must hold at least as many bytes as the tensor. The interpreter keeps it
+    /// until it is dropped; its data is accessed through the tensor, e.g. `tensor_data`.
+    pub fn set_custom_allocation(
+        &mut self,
+        tensor_index: TensorIndex,
+        mut buffer: AlignedBuffer,
+    ) -> Result<()> {
+        let bytes = self
+            .tensor_inner(tensor_index)
+            .ok_or_else(|| invalid_tensor_index(tensor_index))?
+            .bytes;
+        if buffer.len() < bytes {
+            return Err(Error::Allocation(format!(
+                "custom allocation of {} bytes is smaller than the tensor of {bytes} bytes",
+                buffer.len()
+            )));
+        }
+
+        self.error_reporter().clear();
+        let interpreter = self.handle_mut();
+        let data = buffer.as_mut_ptr();
+        let len = buffer.len() as size_t;
+
+        #[allow(clippy::forgetting_copy_types, deprecated, clippy::transmute_num_to_bytes)]
+        let r = unsafe {
+            cpp!([
+                interpreter as "Interpreter*",
+                tensor_index as "int",
+                data as "void*",
+                len as "size_t"
+            ] -> bool as "bool" {
+                TfLiteCustomAllocation allocation{data, len};
+                return interpreter->SetCustomAllocationForTensor(tensor_index, allocation) == kTfLiteOk;
+            })
+        };
+        if !r {
+            return Err(self
+                .error_reporter()
+                .error(Error::Allocation, "failed to set custom allocation"));
         }
+        self.allocations.push(buffer);
+        self.allocate_tensors()
     }
 
     /// Gets model input details
     pub fn get_input_details(&self) -> Result<Vec<TensorInfo>> {
         self.inputs()
             .iter()
-            .map(|index| {
-                self.tensor_info(*index).ok_or_else(|| Error::internal_error("tensor not found"))
-            })
+            .map(|&index| self.tensor_info(index).ok_or_else(|| invalid_tensor_index(index)))
             .collect()
     }
 
@@ -109,9 +280,7 @@ where
     pub fn get_output_details(&self) -> Result<Vec<TensorInfo>> {
         self.outputs()
             .iter()
-            .map(|index| {
-                self.tensor_info(*index).ok_or_else(|| Error::internal_error("tensor not found"))
-            })
+            .map(|&index| self.tensor_info(index).ok_or_else(|| invalid_tensor_index(index)))
             .collect()
     }
 
@@ -129,19 +298,55 @@ where
 
     /// Invoke the interpreter (run the whole graph in dependency order).
     pub fn invoke(&mut self) -> Result<()> {
-        let interpreter = self.handle_mut();
+        let interpreter: &mut bindings::tflite::Interpreter = &mut self.handle;
+        let error_reporter = self.builder.error_reporter();
+        let cancellation = self.cancellation.as_deref();
 
-        #[allow(deprecated)]
-        let r = unsafe {
-            cpp!([interpreter as "Interpreter*"] -> bool as "bool" {
-                return interpreter->Invoke() == kTfLiteOk;
-            })
-        };
-        if r {
-            Ok(())
-        } else {
-            Err(Error::internal_error("failed to invoke interpreter"))
+        checked_invoke(error_reporter, cancellation, "failed to invoke interpreter", || {
+            #[allow(deprecated)]
+            unsafe {
+                cpp!([interpreter as "Interpreter*"] -> bindings::TfLiteStatus as "TfLiteStatus" {
+                    return interpreter->Invoke();
+                })
+            }
+        })
+    }
+
+    /// Like `invoke`, but cancels the invocation with `Error::Cancelled` once it runs
+    /// longer than `timeout`.
+    pub fn invoke_with_timeout(&mut self, timeout: Duration) -> Result<()> {
+        let timer = self.cancellation_mut().timeout.clone();
+        timer.reset();
+
+        let deadline = Deadline::schedule(timer.clone(), Instant::now() + timeout);
+        let result = self.invoke();
+        drop(deadline);
+
+        timer.reset();
+        result
+    }
+
+    /// Attaches `token`, so that cancelling it from another thread aborts `invoke`
+    /// with `Error::Cancelled`. Replaces the previously attached token.
+    pub fn set_cancellation_token(&mut self, token: CancellationToken) {
+        self.cancellation_mut().token = Some(token);
+    }
+
+    pub fn remove_cancellation_token(&mut self) {
+        if let Some(cancellation) = &mut self.cancellation {
+            cancellation.token = None;
+        }
+    }
+
+    /// Returns the tokens checked by the interpreter, installing its cancellation
+    /// function on first use.
+    fn cancellation_mut(&mut self) -> &mut Cancellation {
+        if self.cancellation.is_none() {
+            let cancellation = Box::<Cancellation>::default();
+            cancellation.install(self.handle_mut() as *mut _ as *mut c_void);
+            self.cancellation = Some(cancellation);
         }
+        self.cancellation.as_mut().unwrap()
     }
 
     /// Sets the number of threads available to the interpreter
@@ -159,7 +364,122 @@ where
                 interpreter->SetNumThreads(threads);
             })
         };
-        println!("Set num threads to {threads}");
+    }
+
+    /// Lets float32 operators compute in float16 where the kernel or delegate supports it,
+    /// trading precision for speed.
+    pub fn set_allow_fp16_precision_for_fp32(&mut self, allow: bool) {
+        let interpreter = self.handle_mut();
+
+        #[allow(clippy::forgetting_copy_types, deprecated)]
+        unsafe {
+            cpp!([interpreter as "Interpreter*", allow as "bool"] {
+                interpreter->SetAllowFp16PrecisionForFp32(allow);
+            })
+        };
+    }
+
+    /// Lets a delegate keep outputs in its own buffers, e.g. on the GPU, instead of
+    /// copying them back to the CPU after `invoke`.
+    pub fn set_allow_buffer_handle_output(&mut self, allow: bool) {
+        let interpreter = self.handle_mut();
+
+        #[allow(clippy::forgetting_copy_types, deprecated)]
+        unsafe {
+            cpp!([interpreter as "Interpreter*", allow as "bool"] {
+                interpreter->SetAllowBufferHandleOutput(allow);
+            })
+        };
+    }
+
+    /// Makes `invoke` release the memory of dynamic intermediate tensors as soon as they
+    /// are no longer used, instead of keeping it until the next `invoke`.
+    pub fn ensure_dynamic_tensors_are_released(&mut self) {
+        let interpreter = self.handle_mut();
+
+        #[allow(clippy::forgetting_copy_types, deprecated)]
+        unsafe {
+            cpp!([interpreter as "Interpreter*"] {
+                interpreter->EnsureDynamicTensorsAreReleased();
+            })
+        };
+    }
+
+    /// Returns the memory planned and allocated for the tensors of all subgraphs.
+    pub fn memory_info(&self) -> MemoryInfo {
+        let interpreter = self.handle();
+        let mut info = MemoryInfo::default();
+        let info_ptr = &mut info as *mut MemoryInfo;
+
+        #[allow(clippy::forgetting_copy_types, deprecated)]
+        unsafe {
+            cpp!([interpreter as "const Interpreter*", info_ptr as "SubgraphAllocInfo*"] {
+                for (size_t i = 0; i < interpreter->subgraphs_size(); ++i) {
+                    SubgraphAllocInfo subgraph_info;
+                    interpreter->subgraph(i)->GetMemoryAllocInfo(&subgraph_info);
+                    info_ptr->arena_size += subgraph_info.arena_size;
+                    info_ptr->arena_persist_size += subgraph_info.arena_persist_size;
+                    info_ptr->dynamic_size += subgraph_info.dynamic_size;
+                    info_ptr->resource_size += subgraph_info.resource_size;
+                }
+            })
+        };
+        info
+    }
+
+    /// Applies `delegate` to the graph, letting it take over the nodes it supports,
+    /// and reallocates the tensors.
+    pub fn modify_graph_with_delegate<D: OwnedDelegate + 'static>(
+        &mut self,
+        delegate: D,
+    ) -> Result<()> {
+        self.error_reporter().clear();
+        let interpreter = self.handle_mut();
+        let delegate_handle = delegate.get_delegate_handle();
+
+        #[allow(clippy::forgetting_copy_types, deprecated)]
+        let status = unsafe {
+            cpp!([
+                interpreter as "Interpreter*",
+                delegate_handle as "TfLiteDelegate*"
+            ] -> bindings::TfLiteStatus as "TfLiteStatus" {
+                return interpreter->ModifyGraphWithDelegate(delegate_handle);
+            })
+        };
+        // The graph may reference the delegate even if applying it failed.
+        self.delegates.push(Box::new(delegate));
+        if status == bindings::TfLiteStatus::kTfLiteOk {
+            self.allocate_tensors()
+        } else {
+            Err(self.error_reporter().status_error(
+                status,
+                Error::Delegate,
+                "failed to modify graph with delegate",
+            ))
+        }
+    }
+
+    /// Sets the profiler receiving the events of `invoke`, replacing the previous one.
+    pub fn set_profiler<P: Profiler + 'static>(&mut self, profiler: P) {
+        let profiler = ProfilerHandle::new(Box::new(profiler));
+        self.set_profiler_handle(profiler.handle);
+        self.profiler = Some(profiler);
+    }
+
+    pub fn remove_profiler(&mut self) {
+        self.set_profiler_handle(ptr::null_mut());
+        self.profiler = None;
+    }
+
+    fn set_profiler_handle(&mut self, profiler: *mut c_void) {
+        let interpreter = self.handle_mut();
+
+        #[allow(clippy::forgetting_copy_types, deprecated)]
+        unsafe {
+            cpp!([interpreter as "Interpreter*", profiler as "tflite::Profiler*"] {
+                interpreter->SetProfiler(profiler);
+            })
+        };
     }
 
     /// Read only access to list of inputs.
@@ -200,6 +520,106 @@ where
         unsafe { slice::from_raw_parts(ptr, count) }
     }
 
+    /// Returns the keys of the signatures defined in the model.
+    pub fn signature_keys(&self) -> Vec<String> {
+        let interpreter = self.handle();
+        let mut keys: Vec<String> = Vec::new();
+        let keys_ref = &mut keys;
+
+        #[allow(clippy::forgetting_copy_types, deprecated)]
+        unsafe {
+            cpp!([interpreter as "const Interpreter*", keys_ref as "void*"] {
+                for (const std::string* key : interpreter->signature_keys()) {
+                    const char* ptr = key->data();
+                    size_t size = key->size();
+                    rust!(Interpreter_signature_keys [
+                        keys_ref: &mut Vec<String> as "void*",
+                        ptr: *const u8 as "const char*",
+                        size: size_t as "size_t"
+                    ] {
+                        let key = unsafe { slice::from_raw_parts(ptr, size) };
+                        keys_ref.push(String::from_utf8_lossy(key).into_owned());
+                    });
+                }
+            })
+        };
+        keys
+    }
+
+    /// Returns a runner for the signature named `signature_key`.
+    ///
+    /// The tensors of the signature's subgraph are allocated before the runner is returned.
+    pub fn signature_runner(&mut self, signature_key: &str) -> Result<SignatureRunner<'_>> {
+        let interpreter: &mut bindings::tflite::Interpreter = &mut self.handle;
+        let key = CString::new(signature_key)
+            .map_err(|_| Error::InvalidIndex("signature key contains a nul byte".to_string()))?;
+        let key_ptr = key.as_ptr();
+
+        #[allow(clippy::forgetting_copy_types, deprecated)]
+        let handle = unsafe {
+            cpp!([
+                interpreter as "Interpreter*",
+                key_ptr as "const char*"
+            ] -> *mut bindings::tflite::SignatureRunner as "SignatureRunner*" {
+                return interpreter->GetSignatureRunner(key_ptr);
+            })
+        };
+        if handle.is_null() {
+            return Err(Error::InvalidIndex(format!("signature `{signature_key}` not found")));
+        }
+        SignatureRunner::new(
+            unsafe { &mut *handle },
+            self.builder.error_reporter(),
+            self.cancellation.as_deref(),
+        )
+    }
+
+    /// Returns the number of subgraphs, e.g. the bodies of control flow operators.
+    pub fn subgraphs_size(&self) -> size_t {
+        let interpreter = self.handle();
+
+        #[allow(clippy::forgetting_copy_types, deprecated)]
+        unsafe {
+            cpp!([interpreter as "const Interpreter*"] -> size_t as "size_t" {
+                return interpreter->subgraphs_size();
+            })
+        }
+    }
+
+    /// Returns the subgraph `index`, `0` being the primary subgraph.
+    pub fn subgraph(&mut self, index: usize) -> Result<Subgraph<'_>> {
+        let interpreter: &mut bindings::tflite::Interpreter = &mut self.handle;
+
+        #[allow(clippy::forgetting_copy_types, deprecated, clippy::transmute_num_to_bytes)]
+        let handle = unsafe {
+            cpp!([
+                interpreter as "Interpreter*",
+                index as "size_t"
+            ] -> *mut bindings::tflite::Subgraph as "Subgraph*" {
+                if (index >= interpreter->subgraphs_size()) {
+                    return nullptr;
+                }
+                return interpreter->subgraph(static_cast<int>(index));
+            })
+        };
+        if handle.is_null() {
+            return Err(Error::InvalidIndex(format!("subgraph {index} does not exist")));
+        }
+        Ok(Subgraph::new(
+            unsafe { &mut *handle },
+            index,
+            self.builder.error_reporter(),
+            self.cancellation.as_deref(),
+        ))
+    }
+
+    /// Allocates the tensors of the subgraph `index` and invokes it on its own.
+    pub fn invoke_subgraph(&mut self, index: usize) -> Result<()> {
+        let mut subgraph = self.subgraph(index)?;
+        subgraph.allocate_tensors()?;
+        subgraph.invoke()
+    }
+
     /// Read only access to list of variable tensors.
     pub fn variables(&self) -> &[TensorIndex] {
         let interpreter = self.handle();
@@ -219,6 +639,73 @@ where
         unsafe { slice::from_raw_parts(ptr, count) }
     }
 
+    /// Resets the variable tensors to their initial values, e.g. the state of a recurrent
+    /// model before a new stream.
+    pub fn reset_variable_tensors(&mut self) -> Result<()> {
+        self.error_reporter().clear();
+        let interpreter = self.handle_mut();
+
+        #[allow(deprecated)]
+        let status = unsafe {
+            cpp!([interpreter as "Interpreter*"] -> bindings::TfLiteStatus as "TfLiteStatus" {
+                return interpreter->ResetVariableTensors();
+            })
+        };
+        if status == bindings::TfLiteStatus::kTfLiteOk {
+            Ok(())
+        } else {
+            Err(self.error_reporter().error(Error::Allocation, "failed to reset variable tensors"))
+        }
+    }
+
+    /// Copies the contents of the variable tensors.
+    pub fn save_state(&self) -> Result<InterpreterState> {
+        let tensors = self
+            .variables()
+            .iter()
+            .map(|&tensor_index| {
+                let data = self
+                    .tensor_buffer(tensor_index)
+                    .ok_or_else(|| Error::Allocation("tensor is not allocated".to_string()))?;
+                Ok((tensor_index, data.to_vec()))
+            })
+            .collect::<Result<_>>()?;
+        Ok(InterpreterState { tensors })
+    }
+
+    /// Writes a state saved by `save_state` of an interpreter of the same model
+    /// into the variable tensors, failing with `Error::InvalidState` without writing
+    /// anything if the state does not match them, e.g. lacks one of them.
+    pub fn restore_state(&mut self, state: &InterpreterState) -> Result<()> {
+        if let Some(tensor_index) =
+            self.variables().iter().find(|index| !state.tensors.contains_key(index))
+        {
+            return Err(Error::InvalidState(format!(
+                "the state lacks variable tensor {tensor_index}"
+            )));
+        }
+        for (&tensor_index, data) in &state.tensors {
+            if !self.variables().contains(&tensor_index) {
+                return Err(Error::InvalidState(format!(
+                    "tensor {tensor_index} is not a variable tensor"
+                )));
+            }
+            let size = self.tensor_buffer(tensor_index).map_or(0, |buffer| buffer.len());
+            if size != data.len() {
+                return Err(Error::InvalidState(format!(
+                    "variable tensor {tensor_index} has {size} bytes, the state has {}",
+                    data.len()
+                )));
+            }
+        }
+        for (&tensor_index, data) in &state.tensors {
+            if let Some(buffer) = self.tensor_buffer_mut(tensor_index) {
+                buffer.copy_from_slice(data);
+            }
+        }
+        Ok(())
+    }
+
     /// Return the number of tensors in the model.
     pub fn tensors_size(&self) -> size_t {
         let interpreter = self.handle();
@@ -243,9 +730,89 @@ where
         }
     }
 
+    /// Returns the indices of the nodes in the order they run.
+    ///
+    /// Nodes replaced by a delegate are not part of the plan, the delegate's kernel
+    /// nodes are.
+    pub fn execution_plan(&self) -> &[c_int] {
+        let interpreter = self.handle();
+        let mut count: size_t = 0;
+
+        #[allow(clippy::forgetting_copy_types, deprecated, clippy::transmute_num_to_bytes)]
+        let ptr = unsafe {
+            cpp!([
+                interpreter as "const Interpreter*",
+                mut count as "size_t"
+            ] -> *const c_int as "const int*" {
+                const auto& plan = interpreter->execution_plan();
+                count = plan.size();
+                return plan.data();
+            })
+        };
+        if count == 0 {
+            return &[];
+        }
+        unsafe { slice::from_raw_parts(ptr, count) }
+    }
+
+    /// Describes the node `node_index` and the operator it runs.
+    pub fn node(&self, node_index: usize) -> Result<NodeInfo> {
+        let (node, registration) = self
+            .node_and_registration(node_index)
+            .ok_or_else(|| Error::InvalidIndex(format!("node {node_index} does not exist")))?;
+        let mut info = unsafe { NodeInfo::from_raw(node_index, node, registration) };
+        info.delegated_by = delegate_kernel_of(node_index, self.execution_plan(), |index| {
+            self.node_and_registration(index).map(|(node, _)| node)
+        });
+        Ok(info)
+    }
+
+    fn node_and_registration(
+        &self,
+        node_index: usize,
+    ) -> Option<(&bindings::TfLiteNode, &bindings::TfLiteRegistration)> {
+        let interpreter = self.handle();
+        let mut node: *const bindings::TfLiteNode = ptr::null();
+        let mut registration: *const bindings::TfLiteRegistration = ptr::null();
+
+        #[allow(clippy::forgetting_copy_types, deprecated, clippy::transmute_num_to_bytes)]
+        unsafe {
+            cpp!([
+                interpreter as "const Interpreter*",
+                node_index as "size_t",
+                mut node as "const TfLiteNode*",
+                mut registration as "const TfLiteRegistration*"
+            ] {
+                if (node_index < interpreter->nodes_size()) {
+                    const auto* node_and_registration =
+                        interpreter->node_and_registration(static_cast<int>(node_index));
+                    if (node_and_registration != nullptr) {
+                        node = &node_and_registration->first;
+                        registration = &node_and_registration->second;
+                    }
+                }
+            })
+        };
+        if node.is_null() || registration.is_null() {
+            return None;
+        }
+        Some(unsafe { (&*node, &*registration) })
+    }
+
+    /// Iterates over all nodes of the graph, including those replaced by delegates.
+    pub fn nodes(&self) -> impl Iterator<Item = NodeInfo> + '_ {
+        (0..self.nodes_size()).filter_map(|index| self.node(index).ok())
+    }
+
+    /// Iterates over the nodes of the execution plan, in the order they run.
+    pub fn execution_plan_nodes(&self) -> impl Iterator<Item = NodeInfo> + '_ {
+        self.execution_plan().iter().filter_map(|&index| self.node(index as usize).ok())
+    }
+
     /// Adds `count` tensors, preserving pre-existing Tensor entries.
     /// Return the index of the first new tensor.
     pub fn add_tensors(&mut self, count: size_t) -> Result<TensorIndex> {
+        self.error_reporter().clear();
         let interpreter = self.handle();
         let mut index: TensorIndex = 0;
 
@@ -262,7 +829,7 @@ where
         if result == bindings::TfLiteStatus::kTfLiteOk {
             Ok(index)
         } else {
-            Err(Error::internal_error("failed to add tensors"))
+            Err(self.error_reporter().error(Error::Allocation, "failed to add tensors"))
         }
     }
 
@@ -270,6 +837,7 @@ where
     /// Each index is bound check and this modifies the consistent_ flag of the
     /// interpreter.
     pub fn set_inputs(&mut self, inputs: &[TensorIndex]) -> Result<()> {
+        self.error_reporter().clear();
         let interpreter = self.handle_mut();
         let ptr = inputs.as_ptr();
         let len = inputs.len() as size_t;
@@ -288,7 +856,7 @@ where
         if result == bindings::TfLiteStatus::kTfLiteOk {
             Ok(())
         } else {
-            Err(Error::internal_error("failed to set inputs"))
+            Err(self.error_reporter().error(Error::InvalidIndex, "failed to set inputs"))
         }
     }
 
@@ -296,6 +864,7 @@ where
     /// Each index is bound check and this modifies the consistent_ flag of the
     /// interpreter.
     pub fn set_outputs(&mut self, outputs: &[TensorIndex]) -> Result<()> {
+        self.error_reporter().clear();
         let interpreter = self.handle_mut();
         let ptr = outputs.as_ptr();
         let len = outputs.len() as size_t;
@@ -314,7 +883,7 @@ where
         if result == bindings::TfLiteStatus::kTfLiteOk {
             Ok(())
         } else {
-            Err(Error::internal_error("failed to set outputs"))
+            Err(self.error_reporter().error(Error::InvalidIndex, "failed to set outputs"))
         }
     }
 
@@ -322,6 +891,7 @@ where
     /// Each index is bound check and this modifies the consistent_ flag of the
     /// interpreter.
     pub fn set_variables(&mut self, variables: &[TensorIndex]) -> Result<()> {
+        self.error_reporter().clear();
         let interpreter = self.handle_mut();
         let ptr = variables.as_ptr();
         let len = variables.len() as size_t;
@@ -340,7 +910,7 @@ where
         if result == bindings::TfLiteStatus::kTfLiteOk {
             Ok(())
         } else {
-            Err(Error::internal_error("failed to set variables"))
+            Err(self.error_reporter().error(Error::InvalidIndex, "failed to set variables"))
         }
     }
 
@@ -354,6 +924,7 @@ where
         quantization: QuantizationParams,
         is_variable: bool,
     ) -> Result<()> {
+        self.error_reporter().clear();
         let interpreter = self.handle_mut();
 
         let name_ptr = name.as_ptr();
@@ -384,7 +955,7 @@ where
         if result == bindings::TfLiteStatus::kTfLiteOk {
             Ok(())
         } else {
-            Err(Error::internal_error("failed to set tensor parameters"))
+            Err(self.error_reporter().error(Error::InvalidIndex, "failed to set tensor parameters"))
         }
     }
 
@@ -407,6 +978,26 @@ where
         }
     }
 
+    /// Like `tensor_inner`, but borrows the interpreter mutably, so that the data of the
+    /// tensor may be written.
+    fn tensor_inner_mut(
+        &mut self,
+        tensor_index: TensorIndex,
+    ) -> Option<&mut bindings::TfLiteTensor> {
+        let interpreter = self.handle_mut();
+
+        #[allow(clippy::forgetting_copy_types, deprecated, clippy::transmute_num_to_bytes)]
+        let ptr = unsafe {
+            cpp!([
+                interpreter as "Interpreter*",
+                tensor_index as "int"
+            ] -> *mut bindings::TfLiteTensor as "TfLiteTensor*" {
+                return interpreter->tensor(tensor_index);
+            })
+        };
+        unsafe { ptr.as_mut() }
+    }
+
     pub fn tensor_info(&self, tensor_index: TensorIndex) -> Option<TensorInfo> {
         Some(self.tensor_inner(tensor_index)?.into())
     }
@@ -415,25 +1006,12 @@ where
     where
         T: ElemKindOf,
     {
-        let inner = self
-            .tensor_inner(tensor_index)
-            .ok_or_else(|| Error::internal_error("invalid tensor index"))?;
-        let tensor_info: TensorInfo = inner.into();
-
-        if tensor_info.element_kind != T::elem_kind_of() {
-            return Err(Error::InternalError(format!(
-                "Invalid type reference of `{:?}` to the original type `{:?}`",
-                T::elem_kind_of(),
-                tensor_info.element_kind
-            )));
-        }
+        let inner =
+            self.tensor_inner(tensor_index).ok_or_else(|| invalid_tensor_index(tensor_index))?;
+        check_elem_kind_of::<T>(inner.type_)?;
+        let ptr = tensor_data_ptr::<T>(inner)?;
 
-        Ok(unsafe {
-            slice::from_raw_parts(
-                inner.data.raw_const as *const T,
-                inner.bytes / mem::size_of::<T>(),
-            )
-        })
+        Ok(unsafe { slice::from_raw_parts(ptr, inner.bytes / mem::size_of::<T>()) })
     }
 
     pub fn tensor_data_mut<T>(&mut self, tensor_index: TensorIndex) -> Result<&mut [T]>
@@ -441,42 +1019,826 @@ where
         T: ElemKindOf,
     {
         let inner = self
-            .tensor_inner(tensor_index)
-            .ok_or_else(|| Error::internal_error("invalid tensor index"))?;
-        let tensor_info: TensorInfo = inner.into();
-
-        if tensor_info.element_kind != T::elem_kind_of() {
-            return Err(Error::InternalError(format!(
-                "Invalid type reference of `{:?}` to the original type `{:?}`",
-                T::elem_kind_of(),
-                tensor_info.element_kind
-            )));
-        }
+            .tensor_inner_mut(tensor_index)
+            .ok_or_else(|| invalid_tensor_index(tensor_index))?;
+        check_elem_kind_of::<T>(inner.type_)?;
+        let ptr = tensor_data_ptr::<T>(inner)?;
 
-        Ok(unsafe {
-            slice::from_raw_parts_mut(inner.data.raw as *mut T, inner.bytes / mem::size_of::<T>())
-        })
+        Ok(unsafe { slice::from_raw_parts_mut(ptr, inner.bytes / mem::size_of::<T>()) })
     }
 
     pub fn tensor_buffer(&self, tensor_index: TensorIndex) -> Option<&[u8]> {
         let inner = self.tensor_inner(tensor_index)?;
+        let ptr = tensor_data_ptr::<u8>(inner).ok()?;
 
-        Some(unsafe { slice::from_raw_parts(inner.data.raw_const as *mut u8, inner.bytes) })
+        Some(unsafe { slice::from_raw_parts(ptr, inner.bytes) })
     }
 
     pub fn tensor_buffer_mut(&mut self, tensor_index: TensorIndex) -> Option<&mut [u8]> {
-        let inner = self.tensor_inner(tensor_index)?;
+        let inner = self.tensor_inner_mut(tensor_index)?;
+        let ptr = tensor_data_ptr::<u8>(inner).ok()?;
 
-        Some(unsafe { slice::from_raw_parts_mut(inner.data.raw as *mut u8, inner.bytes) })
+        Some(unsafe { slice::from_raw_parts_mut(ptr, inner.bytes) })
+    }
+
+    /// Copies every tensor holding data, e.g. after `invoke` to inspect the intermediate
+    /// tensors of an interpreter built with `InterpreterOptions::preserve_all_tensors`.
+    /// Without that option the intermediate tensors share memory and are overwritten.
+    pub fn snapshot_tensors(&self) -> BTreeMap<TensorIndex, OwnedTensor> {
+        (0..self.tensors_size() as TensorIndex)
+            .filter_map(|tensor_index| {
+                let data = self.tensor_buffer(tensor_index)?.to_vec();
+                let info = self.tensor_info(tensor_index)?;
+                Some((tensor_index, OwnedTensor { info, data }))
+            })
+            .collect()
+    }
+
+    /// Returns a typed handle to the `index`th input of the graph.
+    ///
+    /// The handle borrows the interpreter mutably, so it cannot outlive a reallocation
+    /// of the tensors, e.g. by `allocate_tensors`, `resize_input_tensor` or `invoke`.
+    pub fn input<T>(&mut self, index: usize) -> Result<InputTensor<'_, T>>
+    where
+        T: ElemKindOf,
+    {
+        let tensor_index = *self
+            .inputs()
+            .get(index)
+            .ok_or_else(|| Error::InvalidIndex(format!("input {index} does not exist")))?;
+        self.input_tensor(tensor_index)
+    }
+
+    /// Like `input`, but finds the input by its tensor name.
+    pub fn input_by_name<T>(&mut self, name: &str) -> Result<InputTensor<'_, T>>
+    where
+        T: ElemKindOf,
+    {
+        let tensor_index = self.input_index(name)?;
+        self.input_tensor(tensor_index)
+    }
+
+    fn input_tensor<T>(&mut self, tensor_index: TensorIndex) -> Result<InputTensor<'_, T>>
+    where
+        T: ElemKindOf,
+    {
+        let info =
+            self.tensor_info(tensor_index).ok_or_else(|| invalid_tensor_index(tensor_index))?;
+        Ok(InputTensor::new(info, self.tensor_data_mut(tensor_index)?))
+    }
+
+    /// Returns a typed handle to the `index`th output of the graph, which borrows the
+    /// interpreter until it is dropped.
+    pub fn output<T>(&self, index: usize) -> Result<OutputTensor<'_, T>>
+    where
+        T: ElemKindOf,
+    {
+        let tensor_index = *self
+            .outputs()
+            .get(index)
+            .ok_or_else(|| Error::InvalidIndex(format!("output {index} does not exist")))?;
+        self.output_tensor(tensor_index)
+    }
+
+    /// Like `output`, but finds the output by its tensor name.
+    pub fn output_by_name<T>(&self, name: &str) -> Result<OutputTensor<'_, T>>
+    where
+        T: ElemKindOf,
+    {
+        let tensor_index = self.output_index(name)?;
+        self.output_tensor(tensor_index)
+    }
+
+    fn output_tensor<T>(&self, tensor_index: TensorIndex) -> Result<OutputTensor<'_, T>>
+    where
+        T: ElemKindOf,
+    {
+        let info =
+            self.tensor_info(tensor_index).ok_or_else(|| invalid_tensor_index(tensor_index))?;
+        Ok(OutputTensor::new(info, self.tensor_data(tensor_index)?))
+    }
+
+    /// Returns the tensor index of the input named `name`.
+    pub fn input_index(&self, name: &str) -> Result<TensorIndex> {
+        self.find_tensor(self.inputs(), name)
+            .ok_or_else(|| Error::InvalidIndex(format!("input `{name}` not found")))
+    }
+
+    /// Returns the tensor index of the output named `name`.
+    pub fn output_index(&self, name: &str) -> Result<TensorIndex> {
+        self.find_tensor(self.outputs(), name)
+            .ok_or_else(|| Error::InvalidIndex(format!("output `{name}` not found")))
+    }
+
+    fn find_tensor(&self, tensor_indices: &[TensorIndex], name: &str) -> Option<TensorIndex> {
+        tensor_indices.iter().copied().find(|&tensor_index| {
+            self.tensor_inner(tensor_index).is_some_and(|inner| {
+                !inner.name.is_null()
+                    && unsafe { CStr::from_ptr(inner.name) }.to_bytes() == name.as_bytes()
+            })
+        })
+    }
+
+    /// Runs the graph once: writes the inputs, given by name, resizing them to the shape
+    /// of their values if needed, invokes the interpreter and copies all outputs by name.
+    ///
+    /// Inputs that are not given keep their current data. Only the dimensions that are
+    /// unknown in the `shape_signature` of an input may change, see
+    /// `resize_input_tensor_strict`. Fails with `Error::InvalidIndex` if two outputs have
+    /// the same name, in which case they must be read by index.
+    pub fn run(&mut self, inputs: &[(&str, TensorValue)]) -> Result<HashMap<String, OwnedTensor>> {
+        let mut tensor_indices = Vec::with_capacity(inputs.len());
+        let mut resized = false;
+        for (name, value) in inputs {
+            let tensor_index = self.input_index(name)?;
+            let info =
+                self.tensor_info(tensor_index).ok_or_else(|| invalid_tensor_index(tensor_index))?;
+            if info.element_kind != value.element_kind() {
+                return Err(Error::TypeMismatch(format!(
+                    "input `{name}` is `{:?}`, the value is `{:?}`",
+                    info.element_kind,
+                    value.element_kind()
+                )));
+            }
+            if info.dims != value.dims() {
+                self.resize_input_tensor_strict(tensor_index, value.dims())?;
+                resized = true;
+            }
+            tensor_indices.push(tensor_index);
+        }
+        if resized {
+            self.allocate_tensors()?;
+        }
+
+        for ((name, value), &tensor_index) in inputs.iter().zip(&tensor_indices) {
+            let buffer = self
+                .tensor_buffer_mut(tensor_index)
+                .ok_or_else(|| Error::Allocation("tensor is not allocated".to_string()))?;
+            if buffer.len() != value.as_bytes().len() {
+                return Err(Error::TypeMismatch(format!(
+                    "input `{name}` has {} bytes, the value has {}",
+                    buffer.len(),
+                    value.as_bytes().len()
+                )));
+            }
+            buffer.copy_from_slice(value.as_bytes());
+        }
+
+        self.invoke()?;
+
+        let mut outputs = HashMap::with_capacity(self.outputs().len());
+        for &tensor_index in self.outputs() {
+            let info =
+                self.tensor_info(tensor_index).ok_or_else(|| invalid_tensor_index(tensor_index))?;
+            let data = self
+                .tensor_buffer(tensor_index)
+                .ok_or_else(|| Error::Allocation("tensor is not allocated".to_string()))?
+                .to_vec();
+            let name = info.name.clone();
+            if outputs.insert(name.clone(), OwnedTensor { info, data }).is_some() {
+                return Err(Error::InvalidIndex(format!("several outputs are named `{name}`")));
+            }
+        }
+        Ok(outputs)
+    }
+
+    /// Writes `values` into a float32 tensor, or quantizes them into a uint8, int8 or int16
+    /// tensor using its quantization parameters.
+    pub fn set_input_f32(&mut self, tensor_index: TensorIndex, values: &[f32]) -> Result<()> {
+        let info =
+            self.tensor_info(tensor_index).ok_or_else(|| invalid_tensor_index(tensor_index))?;
+        check_len(&info, values.len())?;
+
+        fn quantize_into<T: QuantizedElem>(
+            data: &mut [T],
+            info: &TensorInfo,
+            values: &[f32],
+        ) -> Result<()> {
+            data.copy_from_slice(&info.quantization.quantize(&info.dims, values)?);
+            Ok(())
+        }
+
+        match info.element_kind {
+            ElementKind::kTfLiteFloat32 => {
+                self.tensor_data_mut(tensor_index)?.copy_from_slice(values);
+                Ok(())
+            }
+            ElementKind::kTfLiteUInt8 => {
+                quantize_into::<u8>(self.tensor_data_mut(tensor_index)?, &info, values)
+            }
+            ElementKind::kTfLiteInt8 => {
+                quantize_into::<i8>(self.tensor_data_mut(tensor_index)?, &info, values)
+            }
+            ElementKind::kTfLiteInt16 => {
+                quantize_into::<i16>(self.tensor_data_mut(tensor_index)?, &info, values)
+            }
+            kind => Err(Error::TypeMismatch(format!("cannot write f32 values to `{kind:?}`"))),
+        }
+    }
+
+    /// Reads a float32 tensor, or dequantizes a uint8, int8 or int16 tensor
+    /// using its quantization parameters.
+    pub fn output_as_f32(&self, tensor_index: TensorIndex) -> Result<Vec<f32>> {
+        let info =
+            self.tensor_info(tensor_index).ok_or_else(|| invalid_tensor_index(tensor_index))?;
+
+        match info.element_kind {
+            ElementKind::kTfLiteFloat32 => Ok(self.tensor_data::<f32>(tensor_index)?.to_vec()),
+            ElementKind::kTfLiteUInt8 => {
+                info.quantization.dequantize(&info.dims, self.tensor_data::<u8>(tensor_index)?)
+            }
+            ElementKind::kTfLiteInt8 => {
+                info.quantization.dequantize(&info.dims, self.tensor_data::<i8>(tensor_index)?)
+            }
+            ElementKind::kTfLiteInt16 => {
+                info.quantization.dequantize(&info.dims, self.tensor_data::<i16>(tensor_index)?)
+            }
+            kind => Err(Error::TypeMismatch(format!("cannot read `{kind:?}` as f32 values"))),
+        }
+    }
+
+    /// Reads the strings of a `kTfLiteString` tensor.
+    pub fn tensor_strings(&self, tensor_index: TensorIndex) -> Result<Vec<&[u8]>> {
+        let inner =
+            self.tensor_inner(tensor_index).ok_or_else(|| invalid_tensor_index(tensor_index))?;
+        check_string_tensor(inner)?;
+
+        let ptr = unsafe { inner.data.raw_const };
+        if ptr.is_null() {
+            return Ok(Vec::new());
+        }
+        unpack_strings(unsafe { slice::from_raw_parts(ptr as *const u8, inner.bytes) })
+    }
+
+    /// Writes `strings` into the `kTfLiteString` input `tensor_index` using TensorFlow Lite's
+    /// packed layout.
+    ///
+    /// The dimensions of the tensor are kept if they hold exactly `strings.len()` elements.
+    /// Otherwise the tensor is resized to `[strings.len()]` and the tensors are reallocated.
+    pub fn set_tensor_strings<S>(&mut self, tensor_index: TensorIndex, strings: &[S]) -> Result<()>
+    where
+        S: AsRef<[u8]>,
+    {
+        let info = {
+            let inner = self
+                .tensor_inner(tensor_index)
+                .ok_or_else(|| invalid_tensor_index(tensor_index))?;
+            if !self.inputs().contains(&tensor_index) {
+                return Err(Error::InvalidIndex(format!("tensor {tensor_index} is not an input")));
+            }
+            check_string_tensor(inner)?;
+            TensorInfo::from(inner)
+        };
+        if info.dims.iter().product::<usize>() != strings.len() {
+            self.resize_input_tensor(tensor_index, &[strings.len()])?;
+            self.allocate_tensors()?;
+        }
+
+        let interpreter = self.handle_mut();
+        let ptrs: Vec<*const u8> = strings.iter().map(|s| s.as_ref().as_ptr()).collect();
+        let lens: Vec<size_t> = strings.iter().map(|s| s.as_ref().len()).collect();
+        let ptrs_ptr = ptrs.as_ptr();
+        let lens_ptr = lens.as_ptr();
+        let count = strings.len() as size_t;
+
+        #[allow(clippy::forgetting_copy_types, deprecated, clippy::transmute_num_to_bytes)]
+        unsafe {
+            cpp!([
+                interpreter as "Interpreter*",
+                tensor_index as "int",
+                ptrs_ptr as "const char* const*",
+                lens_ptr as "const size_t*",
+                count as "size_t"
+            ] {
+                DynamicBuffer buffer;
+                for (size_t i = 0; i < count; ++i) {
+                    buffer.AddString(ptrs_ptr[i], lens_ptr[i]);
+                }
+                buffer.WriteToTensor(interpreter->tensor(tensor_index), nullptr);
+            })
+        };
+        Ok(())
+    }
+}
+
+/// Runs `invoke`, the `Invoke` of the interpreter, a subgraph or a signature, and converts
+/// its status. A run stopped by the cancellation function fails with `Error::Cancelled`
+/// without being recorded as a failed invocation.
+pub(crate) fn checked_invoke(
+    error_reporter: &ErrorReporter,
+    cancellation: Option<&Cancellation>,
+    summary: &str,
+    invoke: impl FnOnce() -> bindings::TfLiteStatus,
+) -> Result<()> {
+    error_reporter.clear();
+    if let Some(cancellation) = cancellation {
+        cancellation.take_aborted();
+    }
+    let mut status = invoke();
+    // `Invoke` fails with `kTfLiteError` when the cancellation function stops it.
+    if status == bindings::TfLiteStatus::kTfLiteError
+        && cancellation.is_some_and(|c| c.take_aborted())
+    {
+        status = bindings::TfLiteStatus::kTfLiteCancelled;
+    }
+    match status {
+        bindings::TfLiteStatus::kTfLiteOk => Ok(()),
+        bindings::TfLiteStatus::kTfLiteCancelled => {
+            error_reporter.clear();
+            Err(Error::Cancelled)
+        }
+        _ => Err(error_reporter.invoke_error(summary)),
     }
 }
 
+fn invalid_tensor_index(tensor_index: TensorIndex) -> Error {
+    Error::InvalidIndex(format!("tensor {tensor_index} does not exist"))
+}
+
+/// Returns the data pointer of `inner`, failing if it is not allocated,
+/// e.g. because an input was resized since the last `allocate_tensors`.
+fn tensor_data_ptr<T>(inner: &bindings::TfLiteTensor) -> Result<*mut T> {
+    let raw = unsafe { inner.data.raw };
+    if !raw.is_null() {
+        Ok(raw as *mut T)
+    } else if inner.bytes == 0 {
+        Ok(ptr::NonNull::dangling().as_ptr())
+    } else {
+        Err(Error::Allocation("tensor is not allocated".to_string()))
+    }
+}
+
+fn check_string_tensor(inner: &bindings::TfLiteTensor) -> Result<()> {
+    if inner.type_ != ElementKind::kTfLiteString {
+        return Err(Error::TypeMismatch(format!(
+            "Invalid string reference to the original type `{:?}`",
+            inner.type_
+        )));
+    }
+    Ok(())
+}
+
+/// Splits a buffer in the layout of `tflite::DynamicBuffer`: the number of strings `n`,
+/// `n + 1` offsets from the start of the buffer and then the string data, all as `i32`.
+fn unpack_strings(buffer: &[u8]) -> Result<Vec<&[u8]>> {
+    let malformed = || Error::TypeMismatch("malformed string tensor".to_string());
+    let read_i32 = |index: usize| -> Result<usize> {
+        let offset = index * mem::size_of::<i32>();
+        let bytes = buffer.get(offset..offset + mem::size_of::<i32>()).ok_or_else(malformed)?;
+        usize::try_from(i32::from_ne_bytes(bytes.try_into().unwrap())).map_err(|_| malformed())
+    };
+
+    let count = read_i32(0)?;
+    (0..count)
+        .map(|i| {
+            let start = read_i32(i + 1)?;
+            let end = read_i32(i + 2)?;
+            buffer.get(start..end).ok_or_else(malformed)
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::ffi::CString;
+    use std::fmt::Debug;
+    use std::fs;
     use std::sync::Arc;
+    use std::thread;
 
+    use crate::model::stl::memory::UniquePtr;
+    use crate::model::stl::vector::VectorInsert;
+    use crate::model::{
+        BufferT, BuiltinOperator, BuiltinOptionsUnion, Model, OperatorCodeT, OperatorT,
+        SignatureDefT, SubGraphT, TensorMapT, TensorT, TensorType, WhileOptionsT,
+    };
     use crate::ops::builtin::BuiltinOpResolver;
+    use crate::ops::custom::{CustomOp, CustomOptions, OpContext};
+    use crate::ops::MutableOpResolver;
+    use context::{Complex128, Complex64, TensorView, TensorViewMut};
+
+    /// Builds an interpreter of `model` with the builtin operators.
+    fn build(model: FlatBufferModel) -> Interpreter<'static, BuiltinOpResolver> {
+        let builder = InterpreterBuilder::new(model, BuiltinOpResolver::default())
+            .expect("Not able to build builder");
+        builder.build().expect("Not able to build model")
+    }
+
+    /// Builds a model without operators whose only tensor is both its input and its output.
+    fn identity_model(typ: TensorType, shape: &[i32]) -> FlatBufferModel {
+        let model = identity_model_t(typ, shape);
+        FlatBufferModel::build_from_model(&model).expect("Unable to build flatbuffer model")
+    }
+
+    /// Like `identity_model`, but returns the model to be modified before it is built.
+    fn identity_model_t(typ: TensorType, shape: &[i32]) -> Model {
+        let mut model = Model::default();
+        model.version = 3;
+        model.buffers.push_back(UniquePtr::<BufferT>::default());
+
+        let mut tensor: UniquePtr<TensorT> = Default::default();
+        tensor.shape.assign(shape.iter().cloned());
+        tensor.typ = typ;
+        tensor.buffer = 0;
+        tensor.name.assign(&CString::new("tensor").unwrap());
+
+        let mut subgraph: UniquePtr<SubGraphT> = Default::default();
+        subgraph.tensors.push_back(tensor);
+        subgraph.inputs.assign(vec![0]);
+        subgraph.outputs.assign(vec![0]);
+        model.subgraphs.push_back(subgraph);
+        model
+    }
+
+    /// Builds a model running the custom operator `op_name` on a float input of shape `[3]`.
+    fn custom_op_model(op_name: &str, custom_options: &[u8]) -> FlatBufferModel {
+        custom_op_chain_model(op_name, custom_options, 1)
+    }
+
+    /// Like `custom_op_model`, but runs the operator `count` times, each node on the output
+    /// of the previous one.
+    fn custom_op_chain_model(op_name: &str, custom_options: &[u8], count: i32) -> FlatBufferModel {
+        let mut model = Model::default();
+        model.version = 3;
+        model.buffers.push_back(UniquePtr::<BufferT>::default());
+
+        let mut code: UniquePtr<OperatorCodeT> = Default::default();
+        code.builtin_code = BuiltinOperator::BuiltinOperator_CUSTOM;
+        code.deprecated_builtin_code = BuiltinOperator::BuiltinOperator_CUSTOM as u8;
+        code.custom_code.assign(&CString::new(op_name).unwrap());
+        code.version = 1;
+        model.operator_codes.push_back(code);
+
+        let mut subgraph: UniquePtr<SubGraphT> = Default::default();
+        for i in 0..=count {
+            let mut tensor: UniquePtr<TensorT> = Default::default();
+            tensor.shape.assign(vec![3]);
+            tensor.typ = TensorType::TensorType_FLOAT32;
+            tensor.buffer = 0;
+            tensor.name.assign(&CString::new(format!("tensor{i}")).unwrap());
+            subgraph.tensors.push_back(tensor);
+        }
+
+        for i in 0..count {
+            let mut operator: UniquePtr<OperatorT> = Default::default();
+            operator.opcode_index = 0;
+            operator.inputs.assign(vec![i]);
+            operator.outputs.assign(vec![i + 1]);
+            operator.custom_options.assign(custom_options.iter().cloned());
+            subgraph.operators.push_back(operator);
+        }
+
+        subgraph.inputs.assign(vec![0]);
+        subgraph.outputs.assign(vec![count]);
+        model.subgraphs.push_back(subgraph);
+
+        FlatBufferModel::build_from_model(&model).expect("Unable to build flatbuffer model")
+    }
+
+    /// Builds a model adding a variable tensor to a float input of shape `[3]`.
+    fn variable_model() -> FlatBufferModel {
+        let mut model = Model::default();
+        model.version = 3;
+        model.buffers.push_back(UniquePtr::<BufferT>::default());
+
+        let mut code: UniquePtr<OperatorCodeT> = Default::default();
+        code.builtin_code = BuiltinOperator::BuiltinOperator_ADD;
+        code.deprecated_builtin_code = BuiltinOperator::BuiltinOperator_ADD as u8;
+        code.version = 1;
+        model.operator_codes.push_back(code);
+
+        let mut subgraph: UniquePtr<SubGraphT> = Default::default();
+        for (i, name) in ["input", "state", "output"].iter().enumerate() {
+            let mut tensor: UniquePtr<TensorT> = Default::default();
+            tensor.shape.assign(vec![3]);
+            tensor.typ = TensorType::TensorType_FLOAT32;
+            tensor.buffer = 0;
+            tensor.is_variable = i == 1;
+            tensor.name.assign(&CString::new(*name).unwrap());
+            subgraph.tensors.push_back(tensor);
+        }
+
+        let mut operator: UniquePtr<OperatorT> = Default::default();
+        operator.opcode_index = 0;
+        operator.inputs.assign(vec![0, 1]);
+        operator.outputs.assign(vec![2]);
+        subgraph.operators.push_back(operator);
+
+        subgraph.inputs.assign(vec![0]);
+        subgraph.outputs.assign(vec![2]);
+        model.subgraphs.push_back(subgraph);
+
+        FlatBufferModel::build_from_model(&model).expect("Unable to build flatbuffer model")
+    }
+
+    /// Builds a model adding the float inputs `a` and `b` of shape `[3]` into `sum`,
+    /// exposed as the signature `serving_default`.
+    pub(super) fn signature_model() -> FlatBufferModel {
+        let mut model = Model::default();
+        model.version = 3;
+        model.buffers.push_back(UniquePtr::<BufferT>::default());
+
+        let mut code: UniquePtr<OperatorCodeT> = Default::default();
+        code.builtin_code = BuiltinOperator::BuiltinOperator_ADD;
+        code.deprecated_builtin_code = BuiltinOperator::BuiltinOperator_ADD as u8;
+        code.version = 1;
+        model.operator_codes.push_back(code);
+
+        let names = ["a", "b", "sum"];
+        let mut subgraph: UniquePtr<SubGraphT> = Default::default();
+        for name in names {
+            let mut tensor: UniquePtr<TensorT> = Default::default();
+            tensor.shape.assign(vec![3]);
+            tensor.typ = TensorType::TensorType_FLOAT32;
+            tensor.buffer = 0;
+            tensor.name.assign(&CString::new(format!("serving_default_{name}")).unwrap());
+            subgraph.tensors.push_back(tensor);
+        }
+
+        let mut operator: UniquePtr<OperatorT> = Default::default();
+        operator.opcode_index = 0;
+        operator.inputs.assign(vec![0, 1]);
+        operator.outputs.assign(vec![2]);
+        subgraph.operators.push_back(operator);
+
+        subgraph.inputs.assign(vec![0, 1]);
+        subgraph.outputs.assign(vec![2]);
+        model.subgraphs.push_back(subgraph);
+
+        let mut signature: UniquePtr<SignatureDefT> = Default::default();
+        signature.signature_key.assign(&CString::new("serving_default").unwrap());
+        signature.subgraph_index = 0;
+        for (tensor_index, name) in names.iter().enumerate() {
+            let mut map: UniquePtr<TensorMapT> = Default::default();
+            map.name.assign(&CString::new(*name).unwrap());
+            map.tensor_index = tensor_index as u32;
+            if tensor_index < 2 {
+                signature.inputs.push_back(map);
+            } else {
+                signature.outputs.push_back(map);
+            }
+        }
+        model.signature_defs.push_back(signature);
+
+        FlatBufferModel::build_from_model(&model).expect("Unable to build flatbuffer model")
+    }
+
+    /// Builds a tensor of shape `[1]`.
+    fn scalar_tensor(name: &str, typ: TensorType, buffer: u32) -> UniquePtr<TensorT> {
+        let mut tensor: UniquePtr<TensorT> = Default::default();
+        tensor.shape.assign(vec![1]);
+        tensor.typ = typ;
+        tensor.buffer = buffer;
+        tensor.name.assign(&CString::new(name).unwrap());
+        tensor
+    }
+
+    /// Builds a subgraph running the binary operator `opcode_index` on its input and
+    /// the constant in `buffer`.
+    fn binary_subgraph(
+        name: &str,
+        opcode_index: u32,
+        buffer: u32,
+        typ: TensorType,
+    ) -> UniquePtr<SubGraphT> {
+        let mut subgraph: UniquePtr<SubGraphT> = Default::default();
+        subgraph.name.assign(&CString::new(name).unwrap());
+        subgraph.tensors.push_back(scalar_tensor("x", TensorType::TensorType_INT32, 0));
+        subgraph.tensors.push_back(scalar_tensor("constant", TensorType::TensorType_INT32, buffer));
+        subgraph.tensors.push_back(scalar_tensor("y", typ, 0));
+
+        let mut operator: UniquePtr<OperatorT> = Default::default();
+        operator.opcode_index = opcode_index;
+        operator.inputs.assign(vec![0, 1]);
+        operator.outputs.assign(vec![2]);
+        subgraph.operators.push_back(operator);
+
+        subgraph.inputs.assign(vec![0]);
+        subgraph.outputs.assign(vec![2]);
+        subgraph
+    }
+
+    /// Builds a model adding 3 to its int32 input while it is less than 10.
+    fn while_model() -> FlatBufferModel {
+        let mut model = Model::default();
+        model.version = 3;
+        for data in [vec![], 10i32.to_le_bytes().to_vec(), 3i32.to_le_bytes().to_vec()] {
+            let mut buffer = UniquePtr::<BufferT>::default();
+            buffer.data.assign(data);
+            model.buffers.push_back(buffer);
+        }
+
+        for op in [
+            BuiltinOperator::BuiltinOperator_WHILE,
+            BuiltinOperator::BuiltinOperator_LESS,
+            BuiltinOperator::BuiltinOperator_ADD,
+        ] {
+            let mut code: UniquePtr<OperatorCodeT> = Default::default();
+            code.builtin_code = op;
+            code.deprecated_builtin_code = op as u8;
+            code.version = 1;
+            model.operator_codes.push_back(code);
+        }
+
+        let mut main: UniquePtr<SubGraphT> = Default::default();
+        main.name.assign(&CString::new("main").unwrap());
+        main.tensors.push_back(scalar_tensor("input", TensorType::TensorType_INT32, 0));
+        main.tensors.push_back(scalar_tensor("output", TensorType::TensorType_INT32, 0));
+        let mut operator: UniquePtr<OperatorT> = Default::default();
+        operator.opcode_index = 0;
+        operator.inputs.assign(vec![0]);
+        operator.outputs.assign(vec![1]);
+        operator.builtin_options = BuiltinOptionsUnion::WhileOptions();
+        let options: &mut WhileOptionsT = operator.builtin_options.as_mut();
+        options.cond_subgraph_index = 1;
+        options.body_subgraph_index = 2;
+        main.operators.push_back(operator);
+        main.inputs.assign(vec![0]);
+        main.outputs.assign(vec![1]);
+
+        model.subgraphs.push_back(main);
+        model.subgraphs.push_back(binary_subgraph("cond", 1, 1, TensorType::TensorType_BOOL));
+        model.subgraphs.push_back(binary_subgraph("body", 2, 2, TensorType::TensorType_INT32));
+
+        FlatBufferModel::build_from_model(&model).expect("Unable to build flatbuffer model")
+    }
+
+    fn check_elem_kind<T>(typ: TensorType, values: &[T])
+    where
+        T: ElemKindOf + Copy + PartialEq + Debug,
+    {
+        let model = identity_model(typ, &[values.len() as i32]);
+        let mut interpreter = build(model);
+
+        let index = interpreter.inputs()[0];
+        assert_eq!(interpreter.tensor_info(index).unwrap().element_kind, T::elem_kind_of());
+
+        interpreter.tensor_data_mut::<T>(index).unwrap().copy_from_slice(values);
+        interpreter.invoke().unwrap();
+        assert_eq!(interpreter.tensor_data::<T>(interpreter.outputs()[0]).unwrap(), values);
+
+        if T::elem_kind_of() != f32::elem_kind_of() {
+            assert!(interpreter.tensor_data::<f32>(index).is_err());
+        } else {
+            assert!(interpreter.tensor_data::<i32>(index).is_err());
+        }
+    }
+
+    /// Multiplies its input by the `factor` of its custom options.
+    struct Scale;
+
+    impl CustomOp for Scale {
+        type State = f32;
+
+        fn init(&self, options: &CustomOptions<'_>) -> Result<f32> {
+            Ok(options.flexbuffer()?.as_map().idx("factor").as_f32())
+        }
+
+        fn prepare(&self, _factor: &mut f32, context: &mut OpContext<'_>) -> Result<()> {
+            let dims = context.inputs()?[0].info().dims;
+            context.resize_output(0, &dims)
+        }
+
+        fn invoke(
+            &self,
+            factor: &mut f32,
+            inputs: &[TensorView<'_>],
+            outputs: &mut [TensorViewMut<'_>],
+        ) -> Result<()> {
+            let input = inputs[0].data::<f32>()?;
+            for (y, x) in outputs[0].data_mut::<f32>()?.iter_mut().zip(input) {
+                *y = x * *factor;
+            }
+            Ok(())
+        }
+    }
+
+    struct Panicking;
+
+    impl CustomOp for Panicking {
+        type State = ();
+
+        fn init(&self, _options: &CustomOptions<'_>) -> Result<()> {
+            Ok(())
+        }
+
+        fn invoke(
+            &self,
+            _state: &mut (),
+            _inputs: &[TensorView<'_>],
+            _outputs: &mut [TensorViewMut<'_>],
+        ) -> Result<()> {
+            panic!("custom op panicked");
+        }
+    }
+
+    /// Copies its input to its output after sleeping.
+    struct Sleep(Duration);
+
+    impl CustomOp for Sleep {
+        type State = ();
+
+        fn init(&self, _options: &CustomOptions<'_>) -> Result<()> {
+            Ok(())
+        }
+
+        fn prepare(&self, _state: &mut (), context: &mut OpContext<'_>) -> Result<()> {
+            let dims = context.inputs()?[0].info().dims;
+            context.resize_output(0, &dims)
+        }
+
+        fn invoke(
+            &self,
+            _state: &mut (),
+            inputs: &[TensorView<'_>],
+            outputs: &mut [TensorViewMut<'_>],
+        ) -> Result<()> {
+            thread::sleep(self.0);
+            outputs[0].data_mut::<f32>()?.copy_from_slice(inputs[0].data::<f32>()?);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn custom_op() {
+        let mut options = flexbuffers::Builder::default();
+        let mut map = options.start_map();
+        map.push("factor", 3.0f32);
+        map.end_map();
+        let model = custom_op_model("Scale", options.view());
+
+        let builder = InterpreterBuilder::new(&model, BuiltinOpResolver::default()).unwrap();
+        match builder.build().err().expect("unresolved custom op") {
+            Error::UnsupportedOp(message) => assert!(message.contains("Scale"), "{message}"),
+            e => panic!("unexpected error: {e}"),
+        }
+
+        let mut resolver = MutableOpResolver::with_builtin_ops();
+        resolver.add_custom("Scale", 1, Scale).unwrap();
+        let builder = InterpreterBuilder::new(&model, resolver).unwrap();
+        let mut interpreter = builder.build().unwrap();
+
+        interpreter.tensor_data_mut(0).unwrap().copy_from_slice(&[1.0f32, -2.0, 0.5]);
+        interpreter.invoke().unwrap();
+        assert_eq!(interpreter.tensor_data::<f32>(1).unwrap(), &[3.0, -6.0, 1.5]);
+    }
+
+    #[test]
+    fn missing_builtin_op() {
+        let builder = InterpreterBuilder::new(variable_model(), MutableOpResolver::default())
+            .expect("Not able to build builder");
+        match builder.build().err().expect("unresolved builtin op") {
+            Error::UnsupportedOp(message) => assert!(message.contains("ADD"), "{message}"),
+            e => panic!("unexpected error: {e}"),
+        }
+    }
+
+    #[test]
+    fn custom_op_panic() {
+        let model = custom_op_model("Panicking", &[]);
+        let mut resolver = MutableOpResolver::default();
+        resolver.add_custom("Panicking", 1, Panicking).unwrap();
+        let builder = InterpreterBuilder::new(model, resolver).unwrap();
+        let mut interpreter = builder.build().unwrap();
+
+        assert!(interpreter.invoke().is_err());
+    }
+
+    #[test]
+    fn invalid_model() {
+        let mut buffer = fs::read("data/MNISTnet_uint8_quant.tflite").unwrap();
+        buffer.truncate(buffer.len() / 2);
+
+        match FlatBufferModel::build_from_buffer(buffer).err().unwrap() {
+            Error::ModelVerification(message) => {
+                assert!(message.starts_with("failed to build model: "), "{message}")
+            }
+            e => panic!("unexpected error: {e}"),
+        }
+    }
+
+    #[test]
+    fn cancellation() {
+        let model = custom_op_chain_model("Sleep", &[], 3);
+        let mut resolver = MutableOpResolver::default();
+        resolver.add_custom("Sleep", 1, Sleep(Duration::from_millis(100))).unwrap();
+        let builder = InterpreterBuilder::new(model, resolver).unwrap();
+        let mut interpreter = builder.build().unwrap();
+
+        let token = CancellationToken::new();
+        interpreter.set_cancellation_token(token.clone());
+        token.cancel();
+        assert!(matches!(interpreter.invoke(), Err(Error::Cancelled)));
+        assert!(matches!(interpreter.subgraph(0).unwrap().invoke(), Err(Error::Cancelled)));
+        assert!(!interpreter.invoke_failed());
+        token.reset();
+        interpreter.invoke().unwrap();
+
+        let timed_out = interpreter.invoke_with_timeout(Duration::from_millis(10));
+        assert!(matches!(timed_out, Err(Error::Cancelled)));
+        interpreter.invoke_with_timeout(Duration::from_secs(60)).unwrap();
+    }
 
     #[test]
     fn threadsafe_types() {
@@ -491,4 +1853,297 @@ mod tests {
         let interpreter = builder.build().expect("Not able to build model");
         send_sync(&interpreter);
     }
+
+    #[test]
+    fn elem_kind_of_all_numeric_types() {
+        check_elem_kind(TensorType::TensorType_FLOAT32, &[1.5f32, -2.25, 0.0]);
+        check_elem_kind(TensorType::TensorType_FLOAT64, &[1.5f64, -2.25, 0.0]);
+        check_elem_kind(TensorType::TensorType_UINT8, &[0u8, 7, u8::MAX]);
+        check_elem_kind(TensorType::TensorType_UINT16, &[0u16, 7, u16::MAX]);
+        check_elem_kind(TensorType::TensorType_UINT32, &[0u32, 7, u32::MAX]);
+        check_elem_kind(TensorType::TensorType_UINT64, &[0u64, 7, u64::MAX]);
+        check_elem_kind(TensorType::TensorType_INT8, &[i8::MIN, -1, i8::MAX]);
+        check_elem_kind(TensorType::TensorType_INT16, &[i16::MIN, -1, i16::MAX]);
+        check_elem_kind(TensorType::TensorType_INT32, &[i32::MIN, -1, i32::MAX]);
+        check_elem_kind(TensorType::TensorType_INT64, &[i64::MIN, -1, i64::MAX]);
+        check_elem_kind(
+            TensorType::TensorType_COMPLEX64,
+            &[Complex64 { re: 1.0, im: -1.0 }, Complex64 { re: 0.5, im: 2.0 }],
+        );
+        check_elem_kind(
+            TensorType::TensorType_COMPLEX128,
+            &[Complex128 { re: 1.0, im: -1.0 }, Complex128 { re: 0.5, im: 2.0 }],
+        );
+    }
+
+    #[test]
+    fn bool_tensors_as_u8() {
+        let model = identity_model(TensorType::TensorType_BOOL, &[3]);
+        let mut interpreter = build(model);
+
+        let index = interpreter.inputs()[0];
+        assert_eq!(interpreter.tensor_info(index).unwrap().element_kind, ElementKind::kTfLiteBool);
+        interpreter.tensor_data_mut::<u8>(index).unwrap().copy_from_slice(&[1, 0, 1]);
+        interpreter.invoke().unwrap();
+        assert_eq!(interpreter.tensor_data::<u8>(interpreter.outputs()[0]).unwrap(), [1, 0, 1]);
+        assert!(interpreter.tensor_data::<i8>(index).is_err());
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn normalization_from_metadata() {
+        use crate::model::MetadataT;
+        use preprocessing::Normalization;
+
+        let mut model = identity_model_t(TensorType::TensorType_UINT8, &[1, 2, 2, 3]);
+        let mut buffer = UniquePtr::<BufferT>::default();
+        buffer.data.assign(metadata::normalization_metadata(&[127.5], &[127.5, 64.0, 1.0]));
+        model.buffers.push_back(buffer);
+        let mut entry = UniquePtr::<MetadataT>::default();
+        entry.name.assign(&CString::new("TFLITE_METADATA").unwrap());
+        entry.buffer = 1;
+        model.metadata.push_back(entry);
+        let model = FlatBufferModel::build_from_model(&model).unwrap();
+
+        assert_eq!(
+            Normalization::from_metadata(&model, 0).unwrap(),
+            Some(Normalization::MeanStd { mean: [127.5; 3], std: [127.5, 64.0, 1.0] })
+        );
+        assert_eq!(Normalization::from_metadata(&model, 1).unwrap(), None);
+        let model = identity_model(TensorType::TensorType_UINT8, &[1, 2, 2, 3]);
+        assert_eq!(Normalization::from_metadata(&model, 0).unwrap(), None);
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn image_into_unquantized_tensors() {
+        use image::{DynamicImage, GrayImage};
+        use preprocessing::{ImageOptions, Normalization};
+
+        let image = DynamicImage::ImageLuma8(GrayImage::from_raw(2, 1, vec![0, 255]).unwrap());
+        let options = ImageOptions {
+            normalization: Normalization::MeanStd { mean: [100.0; 3], std: [1.0; 3] },
+            ..Default::default()
+        };
+
+        let model = identity_model(TensorType::TensorType_UINT8, &[1, 1, 2, 1]);
+        let mut interpreter = build(model);
+        let index = interpreter.inputs()[0];
+        interpreter.set_input_image(index, &image, &ImageOptions::default()).unwrap();
+        assert_eq!(interpreter.tensor_data::<u8>(index).unwrap(), [0, 255]);
+        interpreter.set_input_image(index, &image, &options).unwrap();
+        assert_eq!(interpreter.tensor_data::<u8>(index).unwrap(), [0, 155]);
+
+        let model = identity_model(TensorType::TensorType_INT8, &[1, 1, 2, 1]);
+        let mut interpreter = build(model);
+        let index = interpreter.inputs()[0];
+        interpreter.set_input_image(index, &image, &options).unwrap();
+        assert_eq!(interpreter.tensor_data::<i8>(index).unwrap(), [-100, 127]);
+
+        let model = identity_model(TensorType::TensorType_INT32, &[1, 1, 2, 1]);
+        let mut interpreter = build(model);
+        let index = interpreter.inputs()[0];
+        assert!(matches!(
+            interpreter.set_input_image(index, &image, &options),
+            Err(Error::TypeMismatch(_))
+        ));
+    }
+
+    #[cfg(feature = "half")]
+    #[test]
+    fn elem_kind_of_half_types() {
+        use half::{bf16, f16};
+
+        check_elem_kind(
+            TensorType::TensorType_FLOAT16,
+            &[f16::from_f32(1.5), f16::from_f32(-2.25), f16::ZERO],
+        );
+        check_elem_kind(
+            TensorType::TensorType_BFLOAT16,
+            &[bf16::from_f32(1.5), bf16::from_f32(-2.25), bf16::ZERO],
+        );
+    }
+
+    #[test]
+    fn string_tensors() {
+        let model = identity_model(TensorType::TensorType_STRING, &[3]);
+        let mut interpreter = build(model);
+        let index = interpreter.inputs()[0];
+
+        interpreter.set_tensor_strings(index, &["hello", "", "world!"]).unwrap();
+        interpreter.invoke().unwrap();
+        assert_eq!(
+            interpreter.tensor_strings(index).unwrap(),
+            vec![&b"hello"[..], &b""[..], &b"world!"[..]]
+        );
+        assert_eq!(interpreter.tensor_info(index).unwrap().dims, vec![3]);
+
+        interpreter.set_tensor_strings(index, &[b"tflite".to_vec()]).unwrap();
+        assert_eq!(interpreter.tensor_strings(index).unwrap(), vec![&b"tflite"[..]]);
+        assert_eq!(interpreter.tensor_info(index).unwrap().dims, vec![1]);
+
+        assert!(matches!(interpreter.tensor_strings(-1), Err(Error::InvalidIndex(_))));
+        assert!(matches!(interpreter.tensor_data::<u8>(index), Err(Error::TypeMismatch(_))));
+
+        let packed: Vec<u8> = [1i32, 12, -1].iter().flat_map(|v| v.to_ne_bytes()).collect();
+        assert!(matches!(unpack_strings(&packed), Err(Error::TypeMismatch(_))));
+
+        let mut interpreter = build(signature_model());
+        let output = interpreter.outputs()[0];
+        assert!(matches!(
+            interpreter.set_tensor_strings(output, &["sum"]),
+            Err(Error::InvalidIndex(_))
+        ));
+    }
+
+    #[test]
+    fn resize_input_tensor() {
+        let model = identity_model(TensorType::TensorType_FLOAT32, &[3]);
+        let mut interpreter = build(model);
+        let index = interpreter.inputs()[0];
+
+        assert!(interpreter.resize_input_tensor_strict(index, &[4]).is_err());
+        interpreter.resize_input_tensor_strict(index, &[3]).unwrap();
+
+        interpreter.resize_input_tensor(index, &[2, 4]).unwrap();
+        assert!(interpreter.tensor_data::<f32>(index).is_err());
+        interpreter.allocate_tensors().unwrap();
+
+        assert_eq!(interpreter.get_output_details().unwrap()[0].dims, vec![2, 4]);
+        assert_eq!(interpreter.tensor_data::<f32>(index).unwrap().len(), 8);
+    }
+
+    #[test]
+    fn custom_allocation() {
+        let model = identity_model(TensorType::TensorType_FLOAT32, &[4]);
+
+        let mut interpreter = build(model);
+        let index = interpreter.inputs()[0];
+
+        assert!(interpreter.set_custom_allocation(index, AlignedBuffer::new(4)).is_err());
+        let buffer = AlignedBuffer::new(4 * mem::size_of::<f32>());
+        let ptr = buffer.as_ptr();
+        interpreter.set_custom_allocation(index, buffer).unwrap();
+
+        interpreter.tensor_data_mut::<f32>(index).unwrap().copy_from_slice(&[1.0, 2.0, 3.0, 4.0]);
+        assert_eq!(interpreter.tensor_buffer(index).unwrap().as_ptr(), ptr);
+        assert_eq!(interpreter.tensor_data::<f32>(index).unwrap(), &[1.0, 2.0, 3.0, 4.0]);
+    }
+
+    #[test]
+    fn variable_state() {
+        let mut interpreter = build(variable_model());
+        assert_eq!(interpreter.variables(), [1]);
+
+        interpreter.tensor_data_mut::<f32>(0).unwrap().copy_from_slice(&[1.0, 2.0, 3.0]);
+        interpreter.tensor_data_mut::<f32>(1).unwrap().copy_from_slice(&[0.5, 0.5, 0.5]);
+        let state = InterpreterState::from_bytes(&interpreter.save_state().unwrap().to_bytes())
+            .expect("Unable to read state");
+
+        // The saved state holds the variable `[0.5; 3]`. After a reset the model adds zero,
+        // and once the state is restored it adds `0.5` again.
+        interpreter.reset_variable_tensors().unwrap();
+        assert_eq!(interpreter.tensor_data::<f32>(1).unwrap(), [0.0; 3]);
+        interpreter.invoke().unwrap();
+        assert_eq!(interpreter.tensor_data::<f32>(2).unwrap(), [1.0, 2.0, 3.0]);
+
+        interpreter.restore_state(&state).unwrap();
+        interpreter.invoke().unwrap();
+        assert_eq!(interpreter.tensor_data::<f32>(2).unwrap(), [1.5, 2.5, 3.5]);
+
+        let mut invalid = state.clone();
+        invalid.tensors.insert(0, vec![0; 12]);
+        assert!(matches!(interpreter.restore_state(&invalid), Err(Error::InvalidState(_))));
+        invalid.tensors = BTreeMap::from([(1, vec![0; 4])]);
+        assert!(matches!(interpreter.restore_state(&invalid), Err(Error::InvalidState(_))));
+        let empty = InterpreterState::default();
+        assert!(matches!(interpreter.restore_state(&empty), Err(Error::InvalidState(_))));
+        assert_eq!(interpreter.tensor_data::<f32>(1).unwrap(), [0.5; 3]);
+    }
+
+    #[test]
+    fn run_by_name() {
+        let model = identity_model(TensorType::TensorType_FLOAT32, &[2]);
+        let mut interpreter = build(model);
+        assert_eq!(interpreter.input_index("tensor").unwrap(), 0);
+        assert!(matches!(interpreter.output_index("missing"), Err(Error::InvalidIndex(_))));
+
+        let value = TensorValue::new(&[2], &[1.0f32, 2.0]).unwrap();
+        let outputs = interpreter.run(&[("tensor", value)]).unwrap();
+        assert_eq!(outputs["tensor"].info.dims, [2]);
+        assert_eq!(outputs["tensor"].values::<f32>().unwrap(), [1.0, 2.0]);
+        assert_eq!(interpreter.output_by_name::<f32>("tensor").unwrap().shape(), [2]);
+
+        // The model has no shape signature, so its dimensions are fixed.
+        let value = TensorValue::new(&[3], &[1.0f32, 2.0, 3.0]).unwrap();
+        assert!(interpreter.run(&[("tensor", value)]).is_err());
+        assert_eq!(interpreter.tensor_info(0).unwrap().dims, [2]);
+
+        let value = TensorValue::new(&[1], &[1i32]).unwrap();
+        assert!(matches!(interpreter.run(&[("tensor", value)]), Err(Error::TypeMismatch(_))));
+        assert!(TensorValue::new(&[2], &[1.0f32]).is_err());
+    }
+
+    #[test]
+    fn unknown_signature() {
+        let model = FlatBufferModel::build_from_file("data/MNISTnet_uint8_quant.tflite")
+            .expect("Unable to build flatbuffer model");
+        let mut interpreter = build(model);
+
+        assert!(interpreter.signature_keys().is_empty());
+        assert!(interpreter.signature_runner("serving_default").is_err());
+    }
+
+    #[test]
+    fn signature_runner() {
+        let mut interpreter = build(signature_model());
+        assert_eq!(interpreter.signature_keys(), ["serving_default"]);
+
+        let mut runner = interpreter.signature_runner("serving_default").unwrap();
+        assert_eq!(runner.signature_key(), "serving_default");
+        assert_eq!(runner.input_names(), ["a", "b"]);
+        assert_eq!(runner.output_names(), ["sum"]);
+        assert_eq!(runner.input_info("a").unwrap().dims, [3]);
+        assert!(runner.input_info("sum").is_err());
+
+        runner.allocate_tensors().unwrap();
+        runner.input_data_mut::<f32>("a").unwrap().copy_from_slice(&[1.0, 2.0, 3.0]);
+        runner.input_data_mut::<f32>("b").unwrap().copy_from_slice(&[10.0, 20.0, 30.0]);
+        runner.invoke().unwrap();
+        assert_eq!(runner.output_data::<f32>("sum").unwrap(), [11.0, 22.0, 33.0]);
+        assert!(runner.output_data::<i32>("sum").is_err());
+    }
+
+    #[test]
+    fn while_loop() {
+        let mut interpreter = build(while_model());
+        assert_eq!(interpreter.subgraphs_size(), 3);
+
+        interpreter.tensor_data_mut::<i32>(0).unwrap()[0] = 1;
+        interpreter.invoke().unwrap();
+        assert_eq!(interpreter.tensor_data::<i32>(1).unwrap(), [10]);
+
+        let main = interpreter.subgraph(0).unwrap();
+        assert_eq!(main.name(), "main");
+        assert_eq!(main.node(0).unwrap().op_name, "WHILE");
+
+        let mut body = interpreter.subgraph(2).unwrap();
+        assert_eq!((body.index(), body.name()), (2, "body".to_string()));
+        assert_eq!((body.inputs(), body.outputs()), (&[0][..], &[2][..]));
+        assert_eq!(body.nodes().map(|node| node.op_name).collect::<Vec<_>>(), ["ADD"]);
+        body.allocate_tensors().unwrap();
+        body.tensor_data_mut::<i32>(0).unwrap()[0] = 4;
+        body.invoke().unwrap();
+        assert_eq!(body.tensor_data::<i32>(2).unwrap(), [7]);
+        assert!(body.tensor_data::<f32>(2).is_err());
+
+        let mut cond = interpreter.subgraph(1).unwrap();
+        cond.allocate_tensors().unwrap();
+        cond.tensor_data_mut::<i32>(0).unwrap()[0] = 1;
+        interpreter.invoke_subgraph(1).unwrap();
+        let cond = interpreter.subgraph(1).unwrap();
+        assert_eq!(cond.tensor_data::<u8>(2).unwrap(), [1]);
+
+        assert!(matches!(interpreter.subgraph(3), Err(Error::InvalidIndex(_))));
+    }
 }
@@ -0,0 +1,119 @@
+use std::ffi::CStr;
+use std::os::raw::c_char;
+
+use libc::c_int;
+
+use super::TensorIndex;
+use crate::bindings;
+
+cpp! {{
+    #include "tensorflow/lite/core/c/common.h"
+    #include "tensorflow/lite/schema/schema_generated.h"
+}}
+
+/// Description of a node of the execution graph and of the operator it runs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NodeInfo {
+    pub index: usize,
+    pub inputs: Vec<TensorIndex>,
+    pub outputs: Vec<TensorIndex>,
+    /// Scratch tensors the kernel allocated for its computation.
+    pub temporaries: Vec<TensorIndex>,
+    /// Tensors of intermediate results recorded for quantization, e.g. of LSTM gates.
+    pub intermediates: Vec<TensorIndex>,
+    /// `BuiltinOperator` code of the operator, `BuiltinOperator_CUSTOM` for custom operators.
+    pub builtin_code: i32,
+    pub custom_name: Option<String>,
+    /// The custom name, or else the name of the builtin operator, e.g. `CONV_2D`.
+    pub op_name: String,
+    pub version: i32,
+    /// Whether the node is the kernel of a delegate, which runs the nodes it replaced.
+    pub delegated: bool,
+    /// For a node replaced by a delegate, the index of the kernel node that runs it.
+    pub delegated_by: Option<usize>,
+}
+
+impl NodeInfo {
+    pub(crate) unsafe fn from_raw(
+        index: usize,
+        node: &bindings::TfLiteNode,
+        registration: &bindings::TfLiteRegistration,
+    ) -> Self {
+        let custom_name = if registration.custom_name.is_null() {
+            None
+        } else {
+            Some(CStr::from_ptr(registration.custom_name).to_string_lossy().into_owned())
+        };
+        let op_name =
+            custom_name.clone().unwrap_or_else(|| builtin_op_name(registration.builtin_code));
+        Self {
+            index,
+            inputs: int_array(node.inputs).to_vec(),
+            outputs: int_array(node.outputs).to_vec(),
+            temporaries: int_array(node.temporaries).to_vec(),
+            intermediates: int_array(node.intermediates).to_vec(),
+            builtin_code: registration.builtin_code,
+            custom_name,
+            op_name,
+            version: registration.version,
+            delegated: !node.delegate.is_null(),
+            delegated_by: None,
+        }
+    }
+}
+
+/// Returns the nodes replaced by `node` if it is the kernel of a delegate, from the
+/// `TfLiteDelegateParams` the kernel is created with.
+unsafe fn replaced_nodes<'a>(node: &'a bindings::TfLiteNode) -> &'a [c_int] {
+    let node = node as *const bindings::TfLiteNode;
+
+    #[allow(clippy::forgetting_copy_types, deprecated)]
+    let nodes = cpp!([node as "const TfLiteNode*"] -> *const bindings::TfLiteIntArray as "const TfLiteIntArray*" {
+        if (node->delegate == nullptr || node->builtin_data == nullptr) {
+            return nullptr;
+        }
+        return reinterpret_cast<const TfLiteDelegateParams*>(node->builtin_data)->nodes_to_replace;
+    });
+    int_array(nodes)
+}
+
+/// Returns the kernel node of the execution plan `plan` that replaced the node
+/// `node_index`, looking the nodes up with `node`.
+pub(crate) fn delegate_kernel_of<'a>(
+    node_index: usize,
+    plan: &[c_int],
+    node: impl Fn(usize) -> Option<&'a bindings::TfLiteNode>,
+) -> Option<usize> {
+    plan.iter().map(|&index| index as usize).find(|&index| {
+        node(index).is_some_and(|kernel| {
+            unsafe { replaced_nodes(kernel) }.contains(&(node_index as c_int))
+        })
+    })
+}
+
+/// Returns the elements of a `TfLiteIntArray`, or an empty slice for a null array.
+pub(crate) unsafe fn int_array<'a>(array: *const bindings::TfLiteIntArray) -> &'a [c_int] {
+    match array.as_ref() {
+        Some(array) => array.data.as_slice(array.size as usize),
+        None => &[],
+    }
+}
+
+/// Returns the name of a `BuiltinOperator`, or its code if it has none.
+pub(crate) fn builtin_op_name(builtin_code: i32) -> String {
+    #[allow(clippy::forgetting_copy_types, deprecated, clippy::transmute_num_to_bytes)]
+    let name = unsafe {
+        cpp!([builtin_code as "int32_t"] -> *const c_char as "const char*" {
+            if (builtin_code < tflite::BuiltinOperator_MIN
+                || builtin_code > tflite::BuiltinOperator_MAX) {
+                return "";
+            }
+            return tflite::EnumNameBuiltinOperator(
+                static_cast<tflite::BuiltinOperator>(builtin_code));
+        })
+    };
+    match unsafe { CStr::from_ptr(name) }.to_str() {
+        Ok(name) if !name.is_empty() => name.to_string(),
+        _ => builtin_code.to_string(),
+    }
+}
@@ -6,13 +6,19 @@ pub trait OpResolver: Send + Sync {
     fn get_resolver_handle(&self) -> &SysOpResolver;
 }
 
-impl<T: OpResolver> OpResolver for Arc<T> {
+impl<T: OpResolver + ?Sized> OpResolver for Box<T> {
     fn get_resolver_handle(&self) -> &SysOpResolver {
         self.as_ref().get_resolver_handle()
     }
 }
 
-impl<'a, T: OpResolver> OpResolver for &'a T {
+impl<T: OpResolver + ?Sized> OpResolver for Arc<T> {
+    fn get_resolver_handle(&self) -> &SysOpResolver {
+        self.as_ref().get_resolver_handle()
+    }
+}
+
+impl<'a, T: OpResolver + ?Sized> OpResolver for &'a T {
     fn get_resolver_handle(&self) -> &SysOpResolver {
         (*self).get_resolver_handle()
     }
@@ -31,6 +31,21 @@ impl OpResolver for Resolver {
     }
 }
 
+impl Resolver {
+    /// Creates a resolver of all builtin operators that does not apply the default delegates,
+    /// such as XNNPACK, so that the graph runs on the builtin kernels only.
+    #[allow(clippy::forgetting_copy_types, deprecated)]
+    pub fn without_default_delegates() -> Self {
+        let handle = unsafe {
+            cpp!([] -> *mut bindings::OpResolver as "OpResolver*" {
+                return new BuiltinOpResolverWithoutDefaultDelegates();
+            })
+        };
+        let handle = unsafe { Box::from_raw(handle) };
+        Self { handle }
+    }
+}
+
 impl Default for Resolver {
     #[allow(clippy::forgetting_copy_types, deprecated)]
     fn default() -> Self {
@@ -0,0 +1,326 @@
+use std::any::Any;
+use std::ffi::{c_void, CStr};
+use std::os::raw::c_char;
+use std::panic::{self, AssertUnwindSafe};
+use std::{ptr, slice};
+
+use libc::{c_int, size_t};
+
+use crate::bindings::{TfLiteContext, TfLiteNode};
+use crate::interpreter::context::{
+    guarded, node_tensor_indices, node_tensors, TensorInfo, TensorView, TensorViewMut,
+};
+use crate::interpreter::node::int_array;
+use crate::{Error, Result};
+
+cpp! {{
+    #include <algorithm>
+
+    #include "tensorflow/lite/builtin_ops.h"
+    #include "tensorflow/lite/core/c/common.h"
+    #include "tensorflow/lite/core/c/operator.h"
+
+    static void* CustomOpInit(
+        void* user_data,
+        TfLiteOpaqueContext* opaque_context,
+        const char* buffer,
+        size_t length
+    ) {
+        TfLiteContext* context = reinterpret_cast<TfLiteContext*>(opaque_context);
+        return rust!(CustomOp_init [
+            user_data: *const c_void as "void*",
+            context: *mut TfLiteContext as "TfLiteContext*",
+            buffer: *const c_char as "const char*",
+            length: size_t as "size_t"
+        ] -> *mut c_void as "void*" {
+            unsafe { init(user_data, context, buffer, length) }
+        });
+    }
+
+    static void CustomOpFree(void* user_data, TfLiteOpaqueContext*, void* buffer) {
+        rust!(CustomOp_free [user_data: *const c_void as "void*", buffer: *mut c_void as "void*"] {
+            unsafe { free(user_data, buffer) }
+        });
+    }
+
+    static TfLiteStatus CustomOpPrepare(
+        void* user_data,
+        TfLiteOpaqueContext* opaque_context,
+        TfLiteOpaqueNode* opaque_node
+    ) {
+        TfLiteContext* context = reinterpret_cast<TfLiteContext*>(opaque_context);
+        TfLiteNode* node = reinterpret_cast<TfLiteNode*>(opaque_node);
+        bool ok = rust!(CustomOp_prepare [
+            user_data: *const c_void as "void*",
+            context: *mut TfLiteContext as "TfLiteContext*",
+            node: *mut TfLiteNode as "TfLiteNode*"
+        ] -> bool as "bool" {
+            unsafe { run(user_data, context, node, false) }
+        });
+        return ok ? kTfLiteOk : kTfLiteError;
+    }
+
+    static TfLiteStatus CustomOpInvoke(
+        void* user_data,
+        TfLiteOpaqueContext* opaque_context,
+        TfLiteOpaqueNode* opaque_node
+    ) {
+        TfLiteContext* context = reinterpret_cast<TfLiteContext*>(opaque_context);
+        TfLiteNode* node = reinterpret_cast<TfLiteNode*>(opaque_node);
+        bool ok = rust!(CustomOp_invoke [
+            user_data: *const c_void as "void*",
+            context: *mut TfLiteContext as "TfLiteContext*",
+            node: *mut TfLiteNode as "TfLiteNode*"
+        ] -> bool as "bool" {
+            unsafe { run(user_data, context, node, true) }
+        });
+        return ok ? kTfLiteOk : kTfLiteError;
+    }
+}}
+
+/// An operator implemented in Rust, registered with `MutableOpResolver::add_custom`.
+///
+/// Errors and panics of the callbacks are reported to the interpreter instead of unwinding
+/// into C++, and make the surrounding `allocate_tensors` or `invoke` fail.
+pub trait CustomOp: Send + Sync + 'static {
+    /// State of a node running the operator.
+    type State: Send + 'static;
+
+    /// Creates the state of a node from the `custom_options` of the operator.
+    fn init(&self, options: &CustomOptions<'_>) -> Result<Self::State>;
+
+    /// Called whenever the tensors are (re)allocated, to validate the inputs
+    /// and resize the outputs.
+    fn prepare(&self, _state: &mut Self::State, _context: &mut OpContext<'_>) -> Result<()> {
+        Ok(())
+    }
+
+    /// Runs the node, reading `inputs` and writing `outputs`, whose dimensions were set
+    /// by the last `prepare`. Omitted optional inputs are skipped.
+    fn invoke(
+        &self,
+        state: &mut Self::State,
+        inputs: &[TensorView<'_>],
+        outputs: &mut [TensorViewMut<'_>],
+    ) -> Result<()>;
+
+    /// Releases the state of a node.
+    fn free(&self, _state: Self::State) {}
+}
+
+/// The `custom_options` of an operator, usually a FlexBuffer map.
+#[derive(Debug, Clone, Copy)]
+pub struct CustomOptions<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> CustomOptions<'a> {
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// Parses the options as a FlexBuffer.
+    pub fn flexbuffer(&self) -> Result<flexbuffers::Reader<&'a [u8]>> {
+        flexbuffers::Reader::get_root(self.bytes)
+            .map_err(|e| Error::TypeMismatch(format!("invalid custom options: {e}")))
+    }
+}
+
+/// Access to the tensors of a node while it is prepared.
+pub struct OpContext<'a> {
+    context: *mut TfLiteContext,
+    node: &'a TfLiteNode,
+}
+
+impl OpContext<'_> {
+    /// Views of the inputs of the node, skipping omitted optional inputs.
+    pub fn inputs(&self) -> Result<Vec<TensorView<'_>>> {
+        let tensors = unsafe { (*self.context).tensors };
+        let indices = unsafe { node_tensor_indices(self.context, self.node.inputs)? };
+        Ok(indices
+            .into_iter()
+            .map(|index| TensorView::new(index, unsafe { &*tensors.add(index as usize) }))
+            .collect())
+    }
+
+    pub fn num_outputs(&self) -> usize {
+        unsafe { int_array(self.node.outputs) }.len()
+    }
+
+    pub fn output_info(&self, index: usize) -> Result<TensorInfo> {
+        let tensor_index = self.output_index(index)?;
+        Ok(unsafe { &*(*self.context).tensors.add(tensor_index as usize) }.into())
+    }
+
+    /// Changes the dimensions of the `index`th output.
+    pub fn resize_output(&mut self, index: usize, dims: &[usize]) -> Result<()> {
+        let context = self.context;
+        let tensor_index = self.output_index(index)?;
+
+        let dims: Vec<c_int> = dims.iter().map(|x| *x as c_int).collect();
+        let dims_ptr = dims.as_ptr();
+        let dims_len = dims.len() as size_t;
+
+        #[allow(clippy::forgetting_copy_types, deprecated, clippy::transmute_num_to_bytes)]
+        let r = unsafe {
+            cpp!([
+                context as "TfLiteContext*",
+                tensor_index as "int",
+                dims_ptr as "const int*",
+                dims_len as "size_t"
+            ] -> bool as "bool" {
+                TfLiteIntArray* size = TfLiteIntArrayCreate(dims_len);
+                std::copy(dims_ptr, dims_ptr + dims_len, size->data);
+                return context->ResizeTensor(context, &context->tensors[tensor_index], size) == kTfLiteOk;
+            })
+        };
+        if r {
+            Ok(())
+        } else {
+            Err(Error::Allocation("failed to resize output tensor".to_string()))
+        }
+    }
+
+    fn output_index(&self, index: usize) -> Result<c_int> {
+        let tensors_size = unsafe { (*self.context).tensors_size };
+        unsafe { int_array(self.node.outputs) }
+            .get(index)
+            .copied()
+            .filter(|&tensor_index| tensor_index >= 0 && (tensor_index as usize) < tensors_size)
+            .ok_or_else(|| Error::InvalidIndex(format!("output {index} does not exist")))
+    }
+}
+
+/// `CustomOp` with its state type erased, so that it can be called from C++.
+pub(crate) trait ErasedOp: Send + Sync {
+    fn init(&self, options: &CustomOptions<'_>) -> Result<Box<dyn Any + Send>>;
+
+    fn prepare(&self, state: &mut (dyn Any + Send), context: &mut OpContext<'_>) -> Result<()>;
+
+    fn invoke(
+        &self,
+        state: &mut (dyn Any + Send),
+        inputs: &[TensorView<'_>],
+        outputs: &mut [TensorViewMut<'_>],
+    ) -> Result<()>;
+
+    fn free(&self, state: Box<dyn Any + Send>);
+}
+
+impl<O: CustomOp> ErasedOp for O {
+    fn init(&self, options: &CustomOptions<'_>) -> Result<Box<dyn Any + Send>> {
+        Ok(Box::new(CustomOp::init(self, options)?))
+    }
+
+    fn prepare(&self, state: &mut (dyn Any + Send), context: &mut OpContext<'_>) -> Result<()> {
+        CustomOp::prepare(self, downcast_state::<O>(state)?, context)
+    }
+
+    fn invoke(
+        &self,
+        state: &mut (dyn Any + Send),
+        inputs: &[TensorView<'_>],
+        outputs: &mut [TensorViewMut<'_>],
+    ) -> Result<()> {
+        CustomOp::invoke(self, downcast_state::<O>(state)?, inputs, outputs)
+    }
+
+    fn free(&self, state: Box<dyn Any + Send>) {
+        if let Ok(state) = state.downcast::<O::State>() {
+            CustomOp::free(self, *state);
+        }
+    }
+}
+
+fn downcast_state<O: CustomOp>(state: &mut (dyn Any + Send)) -> Result<&mut O::State> {
+    state
+        .downcast_mut()
+        .ok_or_else(|| Error::TypeMismatch("custom op state has a wrong type".to_string()))
+}
+
+/// Creates a `TfLiteOperator` calling the `Box<dyn ErasedOp>` behind `user_data`,
+/// which must outlive it.
+pub(crate) fn create_operator(name: &CStr, version: i32, user_data: *mut c_void) -> *mut c_void {
+    let name_ptr = name.as_ptr();
+
+    #[allow(clippy::forgetting_copy_types, deprecated)]
+    unsafe {
+        cpp!([
+            name_ptr as "const char*",
+            version as "int",
+            user_data as "void*"
+        ] -> *mut c_void as "TfLiteOperator*" {
+            TfLiteOperator* op = TfLiteOperatorCreate(kTfLiteBuiltinCustom, name_ptr, version, user_data);
+            TfLiteOperatorSetInitWithData(op, CustomOpInit);
+            TfLiteOperatorSetFreeWithData(op, CustomOpFree);
+            TfLiteOperatorSetPrepareWithData(op, CustomOpPrepare);
+            TfLiteOperatorSetInvokeWithData(op, CustomOpInvoke);
+            return op;
+        })
+    }
+}
+
+pub(crate) fn delete_operator(operator: *mut c_void) {
+    #[allow(clippy::forgetting_copy_types, clippy::useless_transmute, deprecated)]
+    unsafe {
+        cpp!([operator as "TfLiteOperator*"] {
+            TfLiteOperatorDelete(operator);
+        })
+    }
+}
+
+unsafe fn op_ref<'a>(user_data: *const c_void) -> &'a dyn ErasedOp {
+    &**(user_data as *const Box<dyn ErasedOp>)
+}
+
+unsafe fn init(
+    user_data: *const c_void,
+    context: *mut TfLiteContext,
+    buffer: *const c_char,
+    length: size_t,
+) -> *mut c_void {
+    guarded(context, || {
+        let bytes = if buffer.is_null() {
+            &[][..]
+        } else {
+            slice::from_raw_parts(buffer as *const u8, length)
+        };
+        let state = op_ref(user_data).init(&CustomOptions { bytes })?;
+        Ok(Box::into_raw(Box::new(state)) as *mut c_void)
+    })
+    .unwrap_or(ptr::null_mut())
+}
+
+unsafe fn free(user_data: *const c_void, buffer: *mut c_void) {
+    if !buffer.is_null() {
+        let state = *Box::from_raw(buffer as *mut Box<dyn Any + Send>);
+        let _ = panic::catch_unwind(AssertUnwindSafe(|| op_ref(user_data).free(state)));
+    }
+}
+
+unsafe fn run(
+    user_data: *const c_void,
+    context: *mut TfLiteContext,
+    node: *mut TfLiteNode,
+    invoke: bool,
+) -> bool {
+    guarded(context, || {
+        let node = &*node;
+        let state = (node.user_data as *mut Box<dyn Any + Send>)
+            .as_mut()
+            .ok_or_else(|| Error::Invoke("custom op failed to initialize".to_string()))?;
+
+        if invoke {
+            let (inputs, mut outputs) = node_tensors(context, node)?;
+            op_ref(user_data).invoke(state.as_mut(), &inputs, &mut outputs)
+        } else {
+            let mut op_context = OpContext { context, node };
+            op_ref(user_data).prepare(state.as_mut(), &mut op_context)
+        }
+    })
+    .is_some()
+}
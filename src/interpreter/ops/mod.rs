@@ -1 +1,7 @@
 pub mod builtin;
+pub mod custom;
+mod mutable;
+mod selective;
+
+pub use mutable::MutableOpResolver;
+pub use selective::SelectiveOpResolver;
@@ -0,0 +1,135 @@
+use std::ffi::{c_void, CString};
+use std::mem;
+
+use super::custom::{create_operator, delete_operator, CustomOp, ErasedOp};
+use crate::bindings::tflite as bindings;
+use crate::interpreter::op_resolver::OpResolver;
+use crate::{Error, Result};
+
+cpp! {{
+    #include "tensorflow/lite/kernels/register.h"
+    #include "tensorflow/lite/mutable_op_resolver.h"
+
+    using namespace tflite;
+}}
+
+/// An op resolver to which operators implemented in Rust can be added.
+pub struct MutableOpResolver {
+    handle: Box<bindings::OpResolver>,
+    ops: Vec<RegisteredOp>,
+}
+
+/// A custom op together with the `TfLiteOperator` and name the resolver refers to.
+struct RegisteredOp {
+    operator: *mut c_void,
+    _op: Box<Box<dyn ErasedOp>>,
+    _name: CString,
+}
+
+unsafe impl Send for RegisteredOp {}
+unsafe impl Sync for RegisteredOp {}
+
+impl Drop for RegisteredOp {
+    fn drop(&mut self) {
+        delete_operator(self.operator);
+    }
+}
+
+impl Drop for MutableOpResolver {
+    #[allow(clippy::useless_transmute, clippy::forgetting_copy_types, deprecated)]
+    fn drop(&mut self) {
+        let handle = Box::into_raw(mem::take(&mut self.handle));
+        unsafe {
+            cpp!([handle as "OpResolver*"] {
+                delete handle;
+            });
+        }
+    }
+}
+
+impl OpResolver for MutableOpResolver {
+    fn get_resolver_handle(&self) -> &bindings::OpResolver {
+        self.handle.as_ref()
+    }
+}
+
+impl Default for MutableOpResolver {
+    /// Creates a resolver without any operators.
+    #[allow(clippy::forgetting_copy_types, deprecated)]
+    fn default() -> Self {
+        let handle = unsafe {
+            cpp!([] -> *mut bindings::OpResolver as "OpResolver*" {
+                return new MutableOpResolver();
+            })
+        };
+        let handle = unsafe { Box::from_raw(handle) };
+        Self { handle, ops: Vec::new() }
+    }
+}
+
+impl MutableOpResolver {
+    /// Creates a resolver of all builtin operators, to which custom operators can be added.
+    #[allow(clippy::forgetting_copy_types, deprecated)]
+    pub fn with_builtin_ops() -> Self {
+        let handle = unsafe {
+            cpp!([] -> *mut bindings::OpResolver as "OpResolver*" {
+                return new ops::builtin::BuiltinOpResolver();
+            })
+        };
+        let handle = unsafe { Box::from_raw(handle) };
+        Self { handle, ops: Vec::new() }
+    }
+
+    /// Registers `op` as version `version` of the custom operator `name`.
+    pub fn add_custom<O: CustomOp>(&mut self, name: &str, version: i32, op: O) -> Result<()> {
+        let name = CString::new(name)
+            .map_err(|_| Error::InvalidIndex("operator name contains a nul byte".to_string()))?;
+        let op: Box<Box<dyn ErasedOp>> = Box::new(Box::new(op));
+        let user_data = &*op as *const Box<dyn ErasedOp> as *mut c_void;
+        let operator = create_operator(&name, version, user_data);
+        let op = RegisteredOp { operator, _op: op, _name: name };
+
+        let resolver = &mut *self.handle;
+        let name_ptr = op._name.as_ptr();
+
+        #[allow(clippy::forgetting_copy_types, deprecated)]
+        unsafe {
+            cpp!([
+                resolver as "OpResolver*",
+                name_ptr as "const char*",
+                version as "int",
+                operator as "TfLiteOperator*"
+            ] {
+                TfLiteRegistration registration{};
+                registration.registration_external = operator;
+                static_cast<MutableOpResolver*>(resolver)->AddCustom(name_ptr, &registration, version);
+            })
+        };
+        self.ops.push(op);
+        Ok(())
+    }
+
+    /// Registers the kernel `BuiltinOpResolver` has for version `version` of the builtin
+    /// operator `builtin_code`, returning false if there is none.
+    pub(super) fn add_builtin_from_defaults(&mut self, builtin_code: i32, version: i32) -> bool {
+        let resolver = &mut *self.handle;
+
+        #[allow(clippy::forgetting_copy_types, deprecated, clippy::transmute_num_to_bytes)]
+        unsafe {
+            cpp!([
+                resolver as "OpResolver*",
+                builtin_code as "int32_t",
+                version as "int"
+            ] -> bool as "bool" {
+                static const ops::builtin::BuiltinOpResolverWithoutDefaultDelegates builtins;
+                const BuiltinOperator op = static_cast<BuiltinOperator>(builtin_code);
+                const TfLiteRegistration* registration = builtins.FindOp(op, version);
+                if (registration == nullptr) {
+                    return false;
+                }
+                static_cast<MutableOpResolver*>(resolver)->AddBuiltin(op, registration, version);
+                return true;
+            })
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use std::collections::BTreeSet;
+use std::ops::{Deref, DerefMut};
+
+use super::MutableOpResolver;
+use crate::bindings::tflite as bindings;
+use crate::interpreter::node::builtin_op_name;
+use crate::interpreter::op_resolver::OpResolver;
+use crate::model::{BuiltinOperator, Model};
+use crate::{Error, Result};
+
+/// An op resolver registering only the builtin operators, at the versions, that a set of
+/// models uses.
+///
+/// This limits which operators an interpreter accepts, not the size of the binary: the
+/// kernels are looked up at runtime, so every builtin kernel is still linked in. To link
+/// only the kernels of the models, build TensorFlow Lite with the `tflite_custom_cc_library`
+/// Bazel rule given the models, and point `TFLITE_LIB_DIR` at the resulting library.
+/// Custom operators of the models are not registered; add them with `add_custom`.
+/// Like `MutableOpResolver`, it applies no default delegates.
+pub struct SelectiveOpResolver {
+    resolver: MutableOpResolver,
+}
+
+impl Deref for SelectiveOpResolver {
+    type Target = MutableOpResolver;
+
+    fn deref(&self) -> &Self::Target {
+        &self.resolver
+    }
+}
+
+impl DerefMut for SelectiveOpResolver {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.resolver
+    }
+}
+
+impl OpResolver for SelectiveOpResolver {
+    fn get_resolver_handle(&self) -> &bindings::OpResolver {
+        self.resolver.get_resolver_handle()
+    }
+}
+
+impl SelectiveOpResolver {
+    /// Creates a resolver of the builtin operators in the `operator_codes` of `models`,
+    /// failing if one of them is not supported by this build of TensorFlow Lite.
+    pub fn for_models(models: &[&Model]) -> Result<Self> {
+        let ops: BTreeSet<(i32, i32)> = models
+            .iter()
+            .flat_map(|model| model.operator_codes.iter())
+            .map(|code| {
+                // `builtin_code` is 0 in old models, which only set `deprecated_builtin_code`.
+                let builtin_code =
+                    (code.deprecated_builtin_code as i32).max(code.builtin_code as i32);
+                (builtin_code, code.version.max(1))
+            })
+            .filter(|&(builtin_code, _)| {
+                builtin_code != BuiltinOperator::BuiltinOperator_CUSTOM as i32
+            })
+            .collect();
+
+        let mut resolver = MutableOpResolver::default();
+        for (builtin_code, version) in ops {
+            if !resolver.add_builtin_from_defaults(builtin_code, version) {
+                let name = builtin_op_name(builtin_code);
+                return Err(Error::UnsupportedOp(format!("{name} version {version}")));
+            }
+        }
+        Ok(Self { resolver })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{FlatBufferModel, InterpreterBuilder};
+
+    #[test]
+    fn for_models() {
+        let model = Model::from_file("data/MNISTnet_uint8_quant.tflite").unwrap();
+        let resolver = SelectiveOpResolver::for_models(&[&model]).unwrap();
+
+        let flatbuffer = FlatBufferModel::build_from_model(&model).unwrap();
+        let builder = InterpreterBuilder::new(flatbuffer, resolver).unwrap();
+        let mut interpreter = builder.build().unwrap();
+        interpreter.invoke().unwrap();
+    }
+
+    #[test]
+    fn unsupported_version() {
+        let mut model = Model::from_file("data/MNISTnet_uint8_quant.tflite").unwrap();
+        let softmax = model
+            .operator_codes
+            .iter_mut()
+            .find(|code| {
+                code.deprecated_builtin_code == BuiltinOperator::BuiltinOperator_SOFTMAX as u8
+            })
+            .unwrap();
+        softmax.version = 999;
+
+        let e = SelectiveOpResolver::for_models(&[&model]).err().unwrap();
+        assert!(matches!(e, Error::UnsupportedOp(_)));
+        assert_eq!(e.to_string(), "unsupported op: SOFTMAX version 999");
+    }
+}
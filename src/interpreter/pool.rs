@@ -0,0 +1,383 @@
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use libc::c_int;
+
+use super::op_resolver::OpResolver;
+use super::{Interpreter, InterpreterBuilder, ModelRef};
+use crate::{Error, Result};
+
+/// Options of an `InterpreterPool`.
+#[derive(Debug, Clone)]
+pub struct InterpreterPoolOptions {
+    /// Maximum number of interpreters, and thus of concurrent invocations.
+    pub size: usize,
+    /// Number of threads of each interpreter, `-1` to let TensorFlow Lite decide.
+    /// Use `InterpreterPool::with_configure` to vary it between interpreters.
+    pub num_threads: c_int,
+}
+
+type Configure<'a, Op> = dyn Fn(usize, &mut Interpreter<'a, Op>) -> Result<()> + Send + Sync + 'a;
+
+impl Default for InterpreterPoolOptions {
+    /// One single-threaded interpreter per available CPU.
+    fn default() -> Self {
+        let size = thread::available_parallelism().map_or(1, |n| n.get());
+        Self { size, num_threads: 1 }
+    }
+}
+
+/// A pool of interpreters of one model, for running inferences from several threads.
+///
+/// The interpreters share the model and are built with clones of the resolver, so use
+/// a shared resolver such as `&BuiltinOpResolver` or `Arc<BuiltinOpResolver>`.
+/// With an owned or `Arc` model and resolver the pool is `'static`.
+/// Interpreters keep their state, e.g. resized inputs, from one checkout to the next.
+pub struct InterpreterPool<'a, Op>
+where
+    Op: OpResolver + Clone,
+{
+    /// Borrowed or shared, never owned.
+    model: ModelRef<'a>,
+    resolver: Op,
+    options: InterpreterPoolOptions,
+    /// Called on every interpreter the pool builds, with its index.
+    configure: Box<Configure<'a, Op>>,
+    state: Mutex<PoolState<'a, Op>>,
+    returned: Condvar,
+}
+
+struct PoolState<'a, Op>
+where
+    Op: OpResolver,
+{
+    idle: Vec<(usize, Interpreter<'a, Op>)>,
+    /// Indices of the interpreters that were discarded and not rebuilt yet.
+    vacant: Vec<usize>,
+}
+
+/// An interpreter that can be checked out, see `InterpreterPool::take`.
+enum Slot<'a, Op>
+where
+    Op: OpResolver,
+{
+    Idle(usize, Interpreter<'a, Op>),
+    /// The index of a discarded interpreter, whose replacement must be built.
+    Vacant(usize),
+}
+
+impl<'a, Op> InterpreterPool<'a, Op>
+where
+    Op: OpResolver + Clone,
+{
+    /// Creates a pool and builds all its interpreters, failing if one cannot be built.
+    pub fn new<M: Into<ModelRef<'a>>>(
+        model: M,
+        resolver: Op,
+        options: InterpreterPoolOptions,
+    ) -> Result<Self> {
+        Self::with_configure(model, resolver, options, |_, _| Ok(()))
+    }
+
+    /// Like `new`, but calls `configure` on every interpreter the pool builds, including
+    /// the replacements of discarded ones, e.g. to give them different thread counts.
+    /// It is passed the index of the interpreter, from `0` to `size - 1`, which
+    /// a replacement takes over from the discarded interpreter.
+    pub fn with_configure<M, F>(
+        model: M,
+        resolver: Op,
+        options: InterpreterPoolOptions,
+        configure: F,
+    ) -> Result<Self>
+    where
+        M: Into<ModelRef<'a>>,
+        F: Fn(usize, &mut Interpreter<'a, Op>) -> Result<()> + Send + Sync + 'a,
+    {
+        if options.size == 0 {
+            return Err(Error::internal_error("interpreter pool size must be positive"));
+        }
+        let model = match model.into() {
+            ModelRef::Owned(model) => ModelRef::Shared(Arc::new(model)),
+            model => model,
+        };
+        let pool = Self {
+            model,
+            resolver,
+            options,
+            configure: Box::new(configure),
+            state: Mutex::new(PoolState { idle: Vec::new(), vacant: Vec::new() }),
+            returned: Condvar::new(),
+        };
+        let idle = (0..pool.options.size)
+            .rev()
+            .map(|index| Ok((index, pool.build(index)?)))
+            .collect::<Result<Vec<_>>>()?;
+        pool.lock().idle = idle;
+        Ok(pool)
+    }
+
+    pub fn size(&self) -> usize {
+        self.options.size
+    }
+
+    /// Returns the number of interpreters that are not checked out.
+    pub fn available(&self) -> usize {
+        let state = self.lock();
+        state.idle.len() + state.vacant.len()
+    }
+
+    /// Checks out an interpreter, waiting until one is returned if all are in use.
+    pub fn acquire(&self) -> Result<PooledInterpreter<'_, 'a, Op>> {
+        let mut state = self.lock();
+        loop {
+            if let Some(slot) = self.take(&mut state) {
+                drop(state);
+                return self.checkout(slot);
+            }
+            state = self.returned.wait(state).unwrap_or_else(|e| e.into_inner());
+        }
+    }
+
+    /// Like `acquire`, but gives up after `timeout`, returning `None`.
+    pub fn acquire_timeout(
+        &self,
+        timeout: Duration,
+    ) -> Result<Option<PooledInterpreter<'_, 'a, Op>>> {
+        let deadline = Instant::now() + timeout;
+        let mut state = self.lock();
+        loop {
+            if let Some(slot) = self.take(&mut state) {
+                drop(state);
+                return self.checkout(slot).map(Some);
+            }
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Ok(None);
+            }
+            state =
+                self.returned.wait_timeout(state, remaining).unwrap_or_else(|e| e.into_inner()).0;
+        }
+    }
+
+    /// Checks out an interpreter if one is available, without waiting.
+    pub fn try_acquire(&self) -> Result<Option<PooledInterpreter<'_, 'a, Op>>> {
+        let slot = self.take(&mut self.lock());
+        slot.map(|slot| self.checkout(slot)).transpose()
+    }
+
+    /// Takes an idle interpreter, or the index of a discarded one to rebuild, returning
+    /// `None` if all are in use.
+    fn take(&self, state: &mut PoolState<'a, Op>) -> Option<Slot<'a, Op>> {
+        if let Some((index, interpreter)) = state.idle.pop() {
+            Some(Slot::Idle(index, interpreter))
+        } else {
+            state.vacant.pop().map(Slot::Vacant)
+        }
+    }
+
+    fn checkout(&self, slot: Slot<'a, Op>) -> Result<PooledInterpreter<'_, 'a, Op>> {
+        let (index, interpreter) = match slot {
+            Slot::Idle(index, interpreter) => (index, interpreter),
+            Slot::Vacant(index) => {
+                (index, self.build(index).inspect_err(|_| self.release(index, None))?)
+            }
+        };
+        Ok(PooledInterpreter { pool: self, index, interpreter: Some(interpreter), discard: false })
+    }
+
+    fn build(&self, index: usize) -> Result<Interpreter<'a, Op>> {
+        let model = match &self.model {
+            ModelRef::Borrowed(model) => ModelRef::Borrowed(*model),
+            ModelRef::Shared(model) => ModelRef::Shared(model.clone()),
+            ModelRef::Owned(_) => unreachable!("owned models are shared by `new`"),
+        };
+        let mut interpreter = InterpreterBuilder::new(model, self.resolver.clone())?
+            .build_with_threads(self.options.num_threads)?;
+        (self.configure)(index, &mut interpreter)?;
+        Ok(interpreter)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, PoolState<'a, Op>> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Returns the interpreter `index`, or makes it vacant if it was discarded.
+    fn release(&self, index: usize, interpreter: Option<Interpreter<'a, Op>>) {
+        let mut state = self.lock();
+        match interpreter {
+            Some(interpreter) => state.idle.push((index, interpreter)),
+            None => state.vacant.push(index),
+        }
+        self.returned.notify_one();
+    }
+}
+
+/// An interpreter checked out of an `InterpreterPool`, returned to it when dropped.
+///
+/// An interpreter whose invocation failed, other than by cancellation, is discarded
+/// instead of being returned, and the pool builds a replacement when it is next needed.
+/// The interpreter records the failure itself, so this holds for every way of invoking it,
+/// e.g. `invoke`, `run`, a `Subgraph` or a `SignatureRunner`.
+pub struct PooledInterpreter<'p, 'a, Op>
+where
+    Op: OpResolver + Clone,
+{
+    pool: &'p InterpreterPool<'a, Op>,
+    index: usize,
+    interpreter: Option<Interpreter<'a, Op>>,
+    discard: bool,
+}
+
+impl<Op> PooledInterpreter<'_, '_, Op>
+where
+    Op: OpResolver + Clone,
+{
+    /// Returns the index of the interpreter in the pool, see `InterpreterPool::with_configure`.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Makes the pool discard the interpreter instead of reusing it.
+    pub fn mark_unhealthy(&mut self) {
+        self.discard = true;
+    }
+
+    pub fn is_healthy(&self) -> bool {
+        !self.discard && !self.deref().invoke_failed()
+    }
+}
+
+impl<'a, Op> Deref for PooledInterpreter<'_, 'a, Op>
+where
+    Op: OpResolver + Clone,
+{
+    type Target = Interpreter<'a, Op>;
+
+    fn deref(&self) -> &Self::Target {
+        self.interpreter.as_ref().unwrap()
+    }
+}
+
+impl<Op> DerefMut for PooledInterpreter<'_, '_, Op>
+where
+    Op: OpResolver + Clone,
+{
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.interpreter.as_mut().unwrap()
+    }
+}
+
+impl<Op> Drop for PooledInterpreter<'_, '_, Op>
+where
+    Op: OpResolver + Clone,
+{
+    fn drop(&mut self) {
+        let healthy = self.is_healthy();
+        let mut interpreter = self.interpreter.take().filter(|_| healthy);
+        if let Some(interpreter) = &mut interpreter {
+            interpreter.remove_cancellation_token();
+        }
+        self.pool.release(self.index, interpreter);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use crate::interpreter::tests::signature_model;
+    use crate::ops::builtin::BuiltinOpResolver;
+    use crate::{CancellationToken, FlatBufferModel};
+
+    #[test]
+    fn checkout() {
+        let model = FlatBufferModel::build_from_file("data/MNISTnet_uint8_quant.tflite").unwrap();
+        let resolver = BuiltinOpResolver::default();
+        let options = InterpreterPoolOptions { size: 2, num_threads: 1 };
+        let pool = InterpreterPool::new(&model, &resolver, options).unwrap();
+
+        let mut first = pool.acquire().unwrap();
+        let second = pool.try_acquire().unwrap().unwrap();
+        assert_eq!((first.index(), second.index()), (0, 1));
+        assert_eq!(pool.available(), 0);
+        assert!(pool.try_acquire().unwrap().is_none());
+        assert!(pool.acquire_timeout(Duration::from_millis(10)).unwrap().is_none());
+        drop(second);
+        assert_eq!(pool.available(), 1);
+
+        first.invoke().unwrap();
+        first.mark_unhealthy();
+        drop(first);
+        assert_eq!(pool.available(), 2);
+
+        // The replacement of the discarded interpreter takes over its index.
+        let mut interpreters = [pool.acquire().unwrap(), pool.acquire().unwrap()];
+        assert_eq!((interpreters[0].index(), interpreters[1].index()), (1, 0));
+        for interpreter in &mut interpreters {
+            assert!(interpreter.is_healthy());
+            interpreter.invoke().unwrap();
+        }
+    }
+
+    #[test]
+    fn failed_invoke_discards() {
+        let model = FlatBufferModel::build_from_file("data/MNISTnet_uint8_quant.tflite").unwrap();
+        let resolver = BuiltinOpResolver::default();
+        let options = InterpreterPoolOptions { size: 1, num_threads: 1 };
+        let built = AtomicUsize::new(0);
+        let pool =
+            InterpreterPool::with_configure(&model, &resolver, options, |index, interpreter| {
+                assert_eq!(index, 0);
+                built.fetch_add(1, Ordering::SeqCst);
+                interpreter.set_num_threads(2);
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(built.load(Ordering::SeqCst), 1);
+
+        // Fails through the subgraph, bypassing `Interpreter::invoke`.
+        let mut interpreter = pool.acquire().unwrap();
+        let input = interpreter.inputs()[0];
+        interpreter.resize_input_tensor(input, &[2, 28, 28, 1]).unwrap();
+        assert!(interpreter.subgraph(0).unwrap().invoke().is_err());
+        assert!(!interpreter.is_healthy());
+        drop(interpreter);
+
+        let mut interpreter = pool.acquire().unwrap();
+        assert!(interpreter.is_healthy());
+        assert_eq!(built.load(Ordering::SeqCst), 2);
+        interpreter.invoke().unwrap();
+    }
+
+    #[test]
+    fn cancelled_signature_keeps_interpreter() {
+        let model = signature_model();
+        let resolver = BuiltinOpResolver::default();
+        let options = InterpreterPoolOptions { size: 1, num_threads: 1 };
+        let built = AtomicUsize::new(0);
+        let pool = InterpreterPool::with_configure(&model, &resolver, options, |_, _| {
+            built.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        })
+        .unwrap();
+
+        let mut interpreter = pool.acquire().unwrap();
+        let token = CancellationToken::new();
+        interpreter.set_cancellation_token(token.clone());
+        token.cancel();
+        let mut runner = interpreter.signature_runner("serving_default").unwrap();
+        assert!(matches!(runner.invoke(), Err(Error::Cancelled)));
+        drop(runner);
+        assert!(interpreter.is_healthy());
+        drop(interpreter);
+
+        // The interpreter is returned without its token and reused.
+        let mut interpreter = pool.acquire().unwrap();
+        assert_eq!(built.load(Ordering::SeqCst), 1);
+        interpreter.signature_runner("serving_default").unwrap().invoke().unwrap();
+    }
+}
@@ -0,0 +1,330 @@
+//! Preprocessing of images into the NHWC input tensors of vision models.
+
+use image::imageops::FilterType;
+use image::{DynamicImage, GrayImage, RgbImage};
+
+use super::context::ElementKind;
+use super::metadata::{self, METADATA_NAME};
+use super::op_resolver::OpResolver;
+use super::{invalid_tensor_index, FlatBufferModel, Interpreter, TensorIndex};
+use crate::{Error, Result};
+
+/// The part of an image that is resized to the input tensor.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Crop {
+    /// The whole image, stretched to the aspect ratio of the tensor.
+    #[default]
+    None,
+    /// The largest centered region with the aspect ratio of the tensor.
+    Center,
+    Region {
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    },
+}
+
+/// Maps the pixel values, from `0` to `255`, to the values the model expects.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Normalization {
+    /// Keeps the pixel values.
+    #[default]
+    None,
+    /// `(value - mean) / std` per channel, e.g. the `NormalizationOptions` of the model
+    /// metadata, see `Normalization::from_metadata`. Grayscale tensors use the first channel.
+    MeanStd { mean: [f32; 3], std: [f32; 3] },
+}
+
+impl Normalization {
+    /// Reads the `NormalizationOptions` of the `input`th input from the `TFLITE_METADATA`
+    /// of `model`, as written by the TensorFlow Lite Support library. Returns `None` if the
+    /// model has no metadata or the input has no `NormalizationOptions`.
+    ///
+    /// The options have one value for all channels or one per channel.
+    pub fn from_metadata(model: &FlatBufferModel, input: usize) -> Result<Option<Self>> {
+        let Some(buffer) = model.metadata(METADATA_NAME) else {
+            return Ok(None);
+        };
+        let Some((mean, std)) = metadata::input_normalization(buffer, input)? else {
+            return Ok(None);
+        };
+        let per_channel = |values: Vec<f32>| match values[..] {
+            [value] => Ok([value; 3]),
+            [red, green, blue] => Ok([red, green, blue]),
+            _ => Err(Error::ModelVerification(format!(
+                "expected 1 or 3 normalization values, got {}",
+                values.len()
+            ))),
+        };
+        Ok(Some(Normalization::MeanStd { mean: per_channel(mean)?, std: per_channel(std)? }))
+    }
+
+    fn apply(&self, channel: usize, value: u8) -> f32 {
+        match self {
+            Normalization::None => value as f32,
+            Normalization::MeanStd { mean, std } => (value as f32 - mean[channel]) / std[channel],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ImageOptions {
+    /// Interpolation of the resize.
+    pub filter: FilterType,
+    pub crop: Crop,
+    pub normalization: Normalization,
+}
+
+impl Default for ImageOptions {
+    /// Bilinear resize of the whole image, keeping the pixel values.
+    fn default() -> Self {
+        Self { filter: FilterType::Triangle, crop: Crop::None, normalization: Normalization::None }
+    }
+}
+
+/// A raw camera frame.
+#[derive(Debug, Clone, Copy)]
+pub enum Frame<'a> {
+    /// Packed 8-bit RGB.
+    Rgb8 { data: &'a [u8], width: u32, height: u32 },
+    /// Planar YUV 4:2:0: the Y plane followed by the U and V planes.
+    I420 { data: &'a [u8], width: u32, height: u32 },
+    /// The Y plane followed by interleaved U and V samples.
+    Nv12 { data: &'a [u8], width: u32, height: u32 },
+    /// The Y plane followed by interleaved V and U samples, as produced by Android cameras.
+    Nv21 { data: &'a [u8], width: u32, height: u32 },
+}
+
+impl Frame<'_> {
+    /// Converts the frame to RGB, using the BT.601 limited range for YUV frames.
+    pub fn to_image(&self) -> Result<DynamicImage> {
+        let (data, width, height) = match *self {
+            Frame::Rgb8 { data, width, height } => {
+                let image = RgbImage::from_raw(width, height, data.to_vec())
+                    .ok_or_else(|| invalid_frame(data.len(), width, height))?;
+                return Ok(DynamicImage::ImageRgb8(image));
+            }
+            Frame::I420 { data, width, height }
+            | Frame::Nv12 { data, width, height }
+            | Frame::Nv21 { data, width, height } => (data, width, height),
+        };
+        let (w, h) = (width as usize, height as usize);
+        let (chroma_w, chroma_h) = (w.div_ceil(2), h.div_ceil(2));
+        if data.len() < w * h + 2 * chroma_w * chroma_h {
+            return Err(invalid_frame(data.len(), width, height));
+        }
+        let (luma, chroma) = data.split_at(w * h);
+
+        let mut image = RgbImage::new(width, height);
+        for (x, y, pixel) in image.enumerate_pixels_mut() {
+            let (x, y) = (x as usize, y as usize);
+            let c = (y / 2) * chroma_w + x / 2;
+            let (u, v) = match self {
+                Frame::I420 { .. } => (chroma[c], chroma[chroma_w * chroma_h + c]),
+                Frame::Nv12 { .. } => (chroma[2 * c], chroma[2 * c + 1]),
+                _ => (chroma[2 * c + 1], chroma[2 * c]),
+            };
+            pixel.0 = yuv_to_rgb(luma[y * w + x], u, v);
+        }
+        Ok(DynamicImage::ImageRgb8(image))
+    }
+}
+
+fn yuv_to_rgb(y: u8, u: u8, v: u8) -> [u8; 3] {
+    let y = 1.164 * (y as f32 - 16.0);
+    let (u, v) = (u as f32 - 128.0, v as f32 - 128.0);
+    let clamp = |value: f32| value.round().clamp(0.0, 255.0) as u8;
+    [clamp(y + 1.596 * v), clamp(y - 0.392 * u - 0.813 * v), clamp(y + 2.017 * u)]
+}
+
+fn invalid_frame(len: usize, width: u32, height: u32) -> Error {
+    Error::TypeMismatch(format!("{len} bytes are too few for a {width}x{height} frame"))
+}
+
+/// Crops and resizes `image` to `width` x `height` and normalizes its pixels, returning
+/// the values in HWC order with `channels` channels, `1` for grayscale or `3` for RGB.
+pub fn preprocess(
+    image: &DynamicImage,
+    width: u32,
+    height: u32,
+    channels: usize,
+    options: &ImageOptions,
+) -> Result<Vec<f32>> {
+    if width == 0 || height == 0 || image.width() == 0 || image.height() == 0 {
+        return Err(Error::TypeMismatch(format!(
+            "cannot resize a {}x{} image to {width}x{height}",
+            image.width(),
+            image.height()
+        )));
+    }
+    let cropped = match options.crop {
+        Crop::None => None,
+        Crop::Center => {
+            let scale = f64::min(
+                image.width() as f64 / width as f64,
+                image.height() as f64 / height as f64,
+            );
+            let crop_width = ((width as f64 * scale).round() as u32).clamp(1, image.width());
+            let crop_height = ((height as f64 * scale).round() as u32).clamp(1, image.height());
+            let x = (image.width() - crop_width) / 2;
+            let y = (image.height() - crop_height) / 2;
+            Some(image.crop_imm(x, y, crop_width, crop_height))
+        }
+        Crop::Region { x, y, width: crop_width, height: crop_height } => {
+            let fits = crop_width > 0
+                && crop_height > 0
+                && x.checked_add(crop_width).is_some_and(|right| right <= image.width())
+                && y.checked_add(crop_height).is_some_and(|bottom| bottom <= image.height());
+            if !fits {
+                return Err(Error::InvalidIndex(format!(
+                    "crop region {crop_width}x{crop_height} at ({x}, {y}) is outside \
+                     of the {}x{} image",
+                    image.width(),
+                    image.height()
+                )));
+            }
+            Some(image.crop_imm(x, y, crop_width, crop_height))
+        }
+    };
+    let image = cropped.as_ref().unwrap_or(image);
+    let resized = if image.width() == width && image.height() == height {
+        None
+    } else {
+        Some(image.resize_exact(width, height, options.filter))
+    };
+    let image = resized.as_ref().unwrap_or(image);
+
+    let normalization = &options.normalization;
+    match channels {
+        1 => {
+            let pixels: GrayImage = image.to_luma8();
+            Ok(pixels.as_raw().iter().map(|&value| normalization.apply(0, value)).collect())
+        }
+        3 => {
+            let pixels: RgbImage = image.to_rgb8();
+            Ok(pixels
+                .as_raw()
+                .iter()
+                .enumerate()
+                .map(|(i, &value)| normalization.apply(i % 3, value))
+                .collect())
+        }
+        _ => Err(Error::TypeMismatch(format!("cannot convert an image to {channels} channels"))),
+    }
+}
+
+impl<Op> Interpreter<'_, Op>
+where
+    Op: OpResolver,
+{
+    /// Preprocesses `image` into the NHWC input `tensor_index`, which has a batch of one
+    /// and 1 or 3 channels. The values are written as float32, quantized into a quantized
+    /// uint8, int8 or int16 tensor, see `set_input_f32`, or rounded and clamped into an
+    /// unquantized uint8 or int8 tensor. Other tensors fail with `Error::TypeMismatch`.
+    pub fn set_input_image(
+        &mut self,
+        tensor_index: TensorIndex,
+        image: &DynamicImage,
+        options: &ImageOptions,
+    ) -> Result<()> {
+        let info =
+            self.tensor_info(tensor_index).ok_or_else(|| invalid_tensor_index(tensor_index))?;
+        let [1, height, width, channels] = info.dims[..] else {
+            return Err(Error::TypeMismatch(format!(
+                "expected an NHWC tensor with a batch of one, got shape {:?}",
+                info.dims
+            )));
+        };
+        let values = preprocess(image, width as u32, height as u32, channels, options)?;
+        match info.element_kind {
+            ElementKind::kTfLiteFloat32 => self.set_input_f32(tensor_index, &values),
+            ElementKind::kTfLiteUInt8 | ElementKind::kTfLiteInt8 | ElementKind::kTfLiteInt16
+                if info.quantization.is_quantized() =>
+            {
+                self.set_input_f32(tensor_index, &values)
+            }
+            ElementKind::kTfLiteUInt8 => {
+                let data = self.tensor_data_mut::<u8>(tensor_index)?;
+                for (elem, value) in data.iter_mut().zip(values) {
+                    *elem = value.round().clamp(u8::MIN as f32, u8::MAX as f32) as u8;
+                }
+                Ok(())
+            }
+            ElementKind::kTfLiteInt8 => {
+                let data = self.tensor_data_mut::<i8>(tensor_index)?;
+                for (elem, value) in data.iter_mut().zip(values) {
+                    *elem = value.round().clamp(i8::MIN as f32, i8::MAX as f32) as i8;
+                }
+                Ok(())
+            }
+            kind => Err(Error::TypeMismatch(format!(
+                "cannot write an image to an unquantized `{kind:?}` tensor"
+            ))),
+        }
+    }
+
+    /// Like `set_input_image`, for a raw camera frame.
+    pub fn set_input_frame(
+        &mut self,
+        tensor_index: TensorIndex,
+        frame: &Frame<'_>,
+        options: &ImageOptions,
+    ) -> Result<()> {
+        self.set_input_image(tensor_index, &frame.to_image()?, options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn yuv_frames() {
+        // A 2x2 frame of a single color in the three YUV layouts.
+        let (y, u, v) = (81, 90, 240);
+        let expected = yuv_to_rgb(y, u, v);
+        let frames = [
+            Frame::I420 { data: &[y, y, y, y, u, v], width: 2, height: 2 },
+            Frame::Nv12 { data: &[y, y, y, y, u, v], width: 2, height: 2 },
+            Frame::Nv21 { data: &[y, y, y, y, v, u], width: 2, height: 2 },
+        ];
+        for frame in frames {
+            let image = frame.to_image().unwrap().to_rgb8();
+            assert!(image.pixels().all(|pixel| pixel.0 == expected));
+        }
+        assert!(expected[0] > 200 && expected[2] < 50);
+        let short = Frame::I420 { data: &[y; 5], width: 2, height: 2 };
+        assert!(matches!(short.to_image(), Err(Error::TypeMismatch(_))));
+    }
+
+    #[test]
+    fn crop_and_normalize() {
+        let mut image = RgbImage::new(4, 2);
+        image.put_pixel(1, 0, image::Rgb([255, 0, 0]));
+        image.put_pixel(2, 1, image::Rgb([0, 0, 255]));
+        let image = DynamicImage::ImageRgb8(image);
+
+        let options = ImageOptions {
+            filter: FilterType::Nearest,
+            crop: Crop::Center,
+            normalization: Normalization::MeanStd { mean: [127.5; 3], std: [127.5; 3] },
+        };
+        let values = preprocess(&image, 2, 2, 3, &options).unwrap();
+        assert_eq!(values, [1.0, -1.0, -1.0, -1.0, -1.0, -1.0, -1.0, -1.0, -1.0, -1.0, -1.0, 1.0]);
+
+        let options = ImageOptions {
+            crop: Crop::Region { x: 1, y: 0, width: 1, height: 1 },
+            ..Default::default()
+        };
+        assert_eq!(preprocess(&image, 1, 1, 3, &options).unwrap(), [255.0, 0.0, 0.0]);
+        let options = ImageOptions {
+            crop: Crop::Region { x: 3, y: 0, width: 2, height: 1 },
+            ..Default::default()
+        };
+        assert!(matches!(preprocess(&image, 1, 1, 3, &options), Err(Error::InvalidIndex(_))));
+        assert!(preprocess(&image, 1, 1, 4, &ImageOptions::default()).is_err());
+        let options = ImageOptions { crop: Crop::Center, ..Default::default() };
+        assert!(matches!(preprocess(&image, 0, 1, 3, &options), Err(Error::TypeMismatch(_))));
+    }
+}
@@ -0,0 +1,346 @@
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::{c_void, CStr};
+use std::fmt;
+use std::os::raw::c_char;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+cpp! {{
+    #include "tensorflow/lite/core/api/profiler.h"
+
+    class RustProfiler : public tflite::Profiler {
+     public:
+        explicit RustProfiler(void* profiler) : profiler_(profiler) {}
+
+        uint32_t BeginEvent(
+            const char* tag,
+            EventType event_type,
+            int64_t event_metadata1,
+            int64_t event_metadata2
+        ) override {
+            void* profiler = profiler_;
+            uint32_t kind = static_cast<uint32_t>(event_type);
+            return rust!(RustProfiler_begin_event [
+                profiler: *const c_void as "void*",
+                tag: *const c_char as "const char*",
+                kind: u32 as "uint32_t",
+                event_metadata1: i64 as "int64_t",
+                event_metadata2: i64 as "int64_t"
+            ] -> u32 as "uint32_t" {
+                unsafe { begin_event(profiler, tag, kind, event_metadata1, event_metadata2) }
+            });
+        }
+
+        using tflite::Profiler::EndEvent;
+
+        void EndEvent(uint32_t event_handle) override {
+            void* profiler = profiler_;
+            rust!(RustProfiler_end_event [
+                profiler: *const c_void as "void*",
+                event_handle: u32 as "uint32_t"
+            ] {
+                unsafe { end_event(profiler, event_handle) }
+            });
+        }
+
+     private:
+        void* profiler_;
+    };
+}}
+
+/// Kind of a profiling event, after `tflite::Profiler::EventType`.
+///
+/// The telemetry event types are reported through other `tflite::Profiler` hooks than
+/// `BeginEvent`, so they are left as `Other`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventKind {
+    Default,
+    /// Invocation of a node; the metadata are the node and subgraph indices.
+    Operator,
+    /// Invocation of an operator within a delegate; the metadata are the index of the
+    /// operator in the delegate and the subgraph index.
+    DelegateOperator,
+    /// Like `DelegateOperator`, for an operator the delegate profiles itself.
+    DelegateProfiledOperator,
+    /// General runtime instrumentation, e.g. of allocations.
+    Runtime,
+    Other(u32),
+}
+
+impl From<u32> for EventKind {
+    fn from(kind: u32) -> Self {
+        match kind {
+            1 => EventKind::Default,
+            2 => EventKind::Operator,
+            4 => EventKind::DelegateOperator,
+            8 => EventKind::DelegateProfiledOperator,
+            16 => EventKind::Runtime,
+            kind => EventKind::Other(kind),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ProfileEvent<'a> {
+    /// Operator name for operator events, a description otherwise.
+    pub tag: &'a str,
+    pub kind: EventKind,
+    pub metadata1: i64,
+    pub metadata2: i64,
+}
+
+impl ProfileEvent<'_> {
+    fn is_operator(&self) -> bool {
+        self.kind == EventKind::Operator || self.is_delegated()
+    }
+
+    fn is_delegated(&self) -> bool {
+        matches!(self.kind, EventKind::DelegateOperator | EventKind::DelegateProfiledOperator)
+    }
+
+    pub fn node_index(&self) -> Option<usize> {
+        self.is_operator().then_some(self.metadata1 as usize)
+    }
+
+    pub fn subgraph_index(&self) -> Option<usize> {
+        self.is_operator().then_some(self.metadata2 as usize)
+    }
+}
+
+/// Receives the profiling events of an interpreter, see `Interpreter::set_profiler`.
+pub trait Profiler: Send + Sync {
+    /// Called when an event starts. The returned handle is passed to `end_event`.
+    fn begin_event(&self, event: &ProfileEvent<'_>) -> u32;
+
+    fn end_event(&self, event_handle: u32);
+}
+
+/// Owns a `tflite::Profiler` forwarding to a Rust `Profiler`.
+pub(crate) struct ProfilerHandle {
+    pub(crate) handle: *mut c_void,
+    _profiler: Box<Box<dyn Profiler>>,
+}
+
+unsafe impl Send for ProfilerHandle {}
+unsafe impl Sync for ProfilerHandle {}
+
+impl ProfilerHandle {
+    pub(crate) fn new(profiler: Box<dyn Profiler>) -> Self {
+        let profiler = Box::new(profiler);
+        let data = &*profiler as *const Box<dyn Profiler> as *mut c_void;
+
+        #[allow(clippy::forgetting_copy_types, deprecated)]
+        let handle = unsafe {
+            cpp!([data as "void*"] -> *mut c_void as "tflite::Profiler*" {
+                return new RustProfiler(data);
+            })
+        };
+        Self { handle, _profiler: profiler }
+    }
+}
+
+impl Drop for ProfilerHandle {
+    fn drop(&mut self) {
+        let handle = self.handle;
+
+        #[allow(clippy::forgetting_copy_types, clippy::useless_transmute, deprecated)]
+        unsafe {
+            cpp!([handle as "tflite::Profiler*"] {
+                delete handle;
+            });
+        }
+    }
+}
+
+unsafe fn profiler_ref<'a>(profiler: *const c_void) -> &'a dyn Profiler {
+    &**(profiler as *const Box<dyn Profiler>)
+}
+
+unsafe fn begin_event(
+    profiler: *const c_void,
+    tag: *const c_char,
+    kind: u32,
+    metadata1: i64,
+    metadata2: i64,
+) -> u32 {
+    let tag = if tag.is_null() { "" } else { CStr::from_ptr(tag).to_str().unwrap_or_default() };
+    let event = ProfileEvent { tag, kind: kind.into(), metadata1, metadata2 };
+    panic::catch_unwind(AssertUnwindSafe(|| profiler_ref(profiler).begin_event(&event)))
+        .unwrap_or_default()
+}
+
+unsafe fn end_event(profiler: *const c_void, event_handle: u32) {
+    let _ =
+        panic::catch_unwind(AssertUnwindSafe(|| profiler_ref(profiler).end_event(event_handle)));
+}
+
+/// Latency statistics of one node.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpProfile {
+    pub name: String,
+    pub node_index: usize,
+    pub subgraph_index: usize,
+    /// Whether the operator ran within a delegate, in which case `node_index` is the
+    /// index of the operator in the delegate rather than a node of the subgraph.
+    pub delegated: bool,
+    pub count: u32,
+    pub total: Duration,
+    pub max: Duration,
+}
+
+impl OpProfile {
+    pub fn average(&self) -> Duration {
+        self.total.checked_div(self.count).unwrap_or_default()
+    }
+}
+
+/// Per-operator latencies recorded by a `RecordingProfiler`, ordered by subgraph and node,
+/// the operators of delegates after the node of the same index.
+#[derive(Debug, Clone, Default)]
+pub struct ProfileReport {
+    pub ops: Vec<OpProfile>,
+}
+
+impl ProfileReport {
+    pub fn total(&self) -> Duration {
+        self.ops.iter().map(|op| op.total).sum()
+    }
+}
+
+impl fmt::Display for ProfileReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:>8} {:>6} {:<32} {:>6} {:>12} {:>12}",
+            "subgraph", "node", "op", "count", "avg", "max"
+        )?;
+        for op in &self.ops {
+            writeln!(
+                f,
+                "{:>8} {:>6} {:<32} {:>6} {:>12?} {:>12?}",
+                op.subgraph_index,
+                op.node_index,
+                op.name,
+                op.count,
+                op.average(),
+                op.max
+            )?;
+        }
+        write!(f, "total: {:?}", self.total())
+    }
+}
+
+/// Subgraph index, node index and whether the operator ran within a delegate.
+type OpKey = (usize, usize, bool);
+
+#[derive(Default)]
+struct Recording {
+    next_handle: u32,
+    open: HashMap<u32, (OpKey, Instant)>,
+    ops: BTreeMap<OpKey, OpProfile>,
+}
+
+/// A `Profiler` recording the latency of every operator invocation.
+///
+/// Clones share their recording, so keep a clone to read the report of the one
+/// given to the interpreter.
+#[derive(Clone, Default)]
+pub struct RecordingProfiler {
+    recording: Arc<Mutex<Recording>>,
+}
+
+impl RecordingProfiler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn report(&self) -> ProfileReport {
+        let recording = self.recording.lock().unwrap();
+        ProfileReport { ops: recording.ops.values().cloned().collect() }
+    }
+
+    /// Discards everything recorded so far.
+    pub fn reset(&self) {
+        let mut recording = self.recording.lock().unwrap();
+        recording.open.clear();
+        recording.ops.clear();
+    }
+}
+
+impl Profiler for RecordingProfiler {
+    fn begin_event(&self, event: &ProfileEvent<'_>) -> u32 {
+        let (Some(node_index), Some(subgraph_index)) = (event.node_index(), event.subgraph_index())
+        else {
+            return 0;
+        };
+        let delegated = event.is_delegated();
+        let key = (subgraph_index, node_index, delegated);
+        let mut recording = self.recording.lock().unwrap();
+        recording.next_handle = recording.next_handle.wrapping_add(1).max(1);
+        let handle = recording.next_handle;
+        recording.ops.entry(key).or_insert_with(|| OpProfile {
+            name: event.tag.to_string(),
+            node_index,
+            subgraph_index,
+            delegated,
+            count: 0,
+            total: Duration::ZERO,
+            max: Duration::ZERO,
+        });
+        recording.open.insert(handle, (key, Instant::now()));
+        handle
+    }
+
+    fn end_event(&self, event_handle: u32) {
+        let end = Instant::now();
+        let mut recording = self.recording.lock().unwrap();
+        if let Some((key, start)) = recording.open.remove(&event_handle) {
+            if let Some(op) = recording.ops.get_mut(&key) {
+                let elapsed = end - start;
+                op.count += 1;
+                op.total += elapsed;
+                op.max = op.max.max(elapsed);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recording_profiler() {
+        let profiler = RecordingProfiler::new();
+        let event =
+            |tag, kind, node_index| ProfileEvent { tag, kind, metadata1: node_index, metadata2: 0 };
+
+        for _ in 0..2 {
+            let conv = profiler.begin_event(&event("CONV_2D", EventKind::Operator, 1));
+            let other = profiler.begin_event(&event("AllocateTensors", EventKind::Default, 0));
+            assert_eq!(other, 0);
+            profiler.end_event(other);
+            profiler.end_event(conv);
+            let softmax = profiler.begin_event(&event("SOFTMAX", EventKind::Operator, 0));
+            profiler.end_event(softmax);
+        }
+
+        // An operator of a delegate is recorded apart from the node of the same index.
+        let xnn = profiler.begin_event(&event("Convolution", EventKind::DelegateOperator, 1));
+        profiler.end_event(xnn);
+
+        let report = profiler.report();
+        let names: Vec<_> = report.ops.iter().map(|op| op.name.as_str()).collect();
+        assert_eq!(names, ["SOFTMAX", "CONV_2D", "Convolution"]);
+        assert!(report.ops[..2].iter().all(|op| op.count == 2 && !op.delegated));
+        assert!(report.ops[2].count == 1 && report.ops[2].delegated);
+        assert!(report.ops.iter().all(|op| op.max <= op.total));
+        assert!(report.to_string().contains("CONV_2D"));
+        assert_eq!(EventKind::from(8), EventKind::DelegateProfiledOperator);
+        assert_eq!(EventKind::from(16), EventKind::Runtime);
+
+        profiler.reset();
+        assert!(profiler.report().ops.is_empty());
+    }
+}
@@ -0,0 +1,282 @@
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+use std::{mem, slice};
+
+use libc::size_t;
+
+use super::cancellation::Cancellation;
+use super::context::{check_elem_kind_of, ElemKindOf, TensorInfo};
+use super::error_reporter::ErrorReporter;
+use super::{checked_invoke, tensor_data_ptr};
+use crate::bindings::tflite as bindings;
+use crate::bindings::{TfLiteStatus, TfLiteTensor};
+use crate::{Error, Result};
+
+cpp! {{
+    #include "tensorflow/lite/interpreter.h"
+    #include "tensorflow/lite/signature_runner.h"
+
+    using namespace tflite;
+}}
+
+/// Runs a single signature of the model, addressing its tensors by their signature names.
+///
+/// The runner is owned by the `Interpreter` it was obtained from and borrows it mutably.
+pub struct SignatureRunner<'i> {
+    handle: &'i mut bindings::SignatureRunner,
+    error_reporter: &'i ErrorReporter,
+    cancellation: Option<&'i Cancellation>,
+}
+
+impl<'i> SignatureRunner<'i> {
+    pub(crate) fn new(
+        handle: &'i mut bindings::SignatureRunner,
+        error_reporter: &'i ErrorReporter,
+        cancellation: Option<&'i Cancellation>,
+    ) -> Result<Self> {
+        let mut runner = Self { handle, error_reporter, cancellation };
+        runner.allocate_tensors()?;
+        Ok(runner)
+    }
+
+    /// Returns the key of this signature.
+    pub fn signature_key(&self) -> String {
+        let runner = &*self.handle;
+
+        #[allow(clippy::forgetting_copy_types, deprecated)]
+        let key = unsafe {
+            CStr::from_ptr(
+                cpp!([runner as "const SignatureRunner*"] -> *const c_char as "const char*" {
+                    return runner->signature_key().c_str();
+                }),
+            )
+        };
+        key.to_string_lossy().into_owned()
+    }
+
+    /// Returns the input names of this signature.
+    pub fn input_names(&self) -> Vec<String> {
+        let runner = &*self.handle;
+        let mut count: size_t = 0;
+
+        #[allow(clippy::forgetting_copy_types, deprecated, clippy::transmute_num_to_bytes)]
+        let ptr = unsafe {
+            cpp!([
+                runner as "const SignatureRunner*",
+                mut count as "size_t"
+            ] -> *const *const c_char as "const char* const*" {
+                const auto& names = runner->input_names();
+                count = names.size();
+                return names.data();
+            })
+        };
+        Self::names(ptr, count)
+    }
+
+    /// Returns the output names of this signature.
+    pub fn output_names(&self) -> Vec<String> {
+        let runner = &*self.handle;
+        let mut count: size_t = 0;
+
+        #[allow(clippy::forgetting_copy_types, deprecated, clippy::transmute_num_to_bytes)]
+        let ptr = unsafe {
+            cpp!([
+                runner as "const SignatureRunner*",
+                mut count as "size_t"
+            ] -> *const *const c_char as "const char* const*" {
+                const auto& names = runner->output_names();
+                count = names.size();
+                return names.data();
+            })
+        };
+        Self::names(ptr, count)
+    }
+
+    fn names(ptr: *const *const c_char, count: size_t) -> Vec<String> {
+        if count == 0 {
+            return Vec::new();
+        }
+        unsafe { slice::from_raw_parts(ptr, count) }
+            .iter()
+            .map(|&name| unsafe { CStr::from_ptr(name) }.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    fn input_inner(&self, name: &str) -> Result<&TfLiteTensor> {
+        let runner = &*self.handle;
+        let c_name = Self::c_name(name)?;
+        let name_ptr = c_name.as_ptr();
+
+        #[allow(clippy::forgetting_copy_types, deprecated)]
+        let ptr = unsafe {
+            cpp!([
+                runner as "SignatureRunner*",
+                name_ptr as "const char*"
+            ] -> *const TfLiteTensor as "const TfLiteTensor*" {
+                return runner->input_tensor(name_ptr);
+            })
+        };
+        if ptr.is_null() {
+            return Err(Error::InvalidIndex(format!("signature input `{name}` not found")));
+        }
+        Ok(unsafe { &*ptr })
+    }
+
+    /// Like `input_inner`, but borrows the runner mutably, so that the data of the
+    /// tensor may be written.
+    fn input_inner_mut(&mut self, name: &str) -> Result<&mut TfLiteTensor> {
+        let runner = &mut *self.handle;
+        let c_name = Self::c_name(name)?;
+        let name_ptr = c_name.as_ptr();
+
+        #[allow(clippy::forgetting_copy_types, deprecated)]
+        let ptr = unsafe {
+            cpp!([
+                runner as "SignatureRunner*",
+                name_ptr as "const char*"
+            ] -> *mut TfLiteTensor as "TfLiteTensor*" {
+                return runner->input_tensor(name_ptr);
+            })
+        };
+        if ptr.is_null() {
+            return Err(Error::InvalidIndex(format!("signature input `{name}` not found")));
+        }
+        Ok(unsafe { &mut *ptr })
+    }
+
+    fn output_inner(&self, name: &str) -> Result<&TfLiteTensor> {
+        let runner = &*self.handle;
+        let c_name = Self::c_name(name)?;
+        let name_ptr = c_name.as_ptr();
+
+        #[allow(clippy::forgetting_copy_types, deprecated)]
+        let ptr = unsafe {
+            cpp!([
+                runner as "const SignatureRunner*",
+                name_ptr as "const char*"
+            ] -> *const TfLiteTensor as "const TfLiteTensor*" {
+                return runner->output_tensor(name_ptr);
+            })
+        };
+        if ptr.is_null() {
+            return Err(Error::InvalidIndex(format!("signature output `{name}` not found")));
+        }
+        Ok(unsafe { &*ptr })
+    }
+
+    fn c_name(name: &str) -> Result<CString> {
+        CString::new(name)
+            .map_err(|_| Error::InvalidIndex("tensor name contains a nul byte".to_string()))
+    }
+
+    /// Gets the details of the input named `name`.
+    pub fn input_info(&self, name: &str) -> Result<TensorInfo> {
+        Ok(self.input_inner(name)?.into())
+    }
+
+    /// Gets the details of the output named `name`.
+    pub fn output_info(&self, name: &str) -> Result<TensorInfo> {
+        Ok(self.output_inner(name)?.into())
+    }
+
+    pub fn input_data<T>(&self, name: &str) -> Result<&[T]>
+    where
+        T: ElemKindOf,
+    {
+        let inner = self.input_inner(name)?;
+        check_elem_kind_of::<T>(inner.type_)?;
+        let ptr = tensor_data_ptr::<T>(inner)?;
+
+        Ok(unsafe { slice::from_raw_parts(ptr, inner.bytes / mem::size_of::<T>()) })
+    }
+
+    pub fn input_data_mut<T>(&mut self, name: &str) -> Result<&mut [T]>
+    where
+        T: ElemKindOf,
+    {
+        let inner = self.input_inner_mut(name)?;
+        check_elem_kind_of::<T>(inner.type_)?;
+        let ptr = tensor_data_ptr::<T>(inner)?;
+
+        Ok(unsafe { slice::from_raw_parts_mut(ptr, inner.bytes / mem::size_of::<T>()) })
+    }
+
+    pub fn output_data<T>(&self, name: &str) -> Result<&[T]>
+    where
+        T: ElemKindOf,
+    {
+        let inner = self.output_inner(name)?;
+        check_elem_kind_of::<T>(inner.type_)?;
+        let ptr = tensor_data_ptr::<T>(inner)?;
+
+        Ok(unsafe { slice::from_raw_parts(ptr, inner.bytes / mem::size_of::<T>()) })
+    }
+
+    /// Changes the dimensionality of the input named `name`.
+    /// `allocate_tensors` must be called before the tensors are accessed again.
+    pub fn resize_input_tensor(&mut self, name: &str, dims: &[usize]) -> Result<()> {
+        self.input_inner(name)?;
+        self.error_reporter.clear();
+        let runner = &mut *self.handle;
+        let c_name = Self::c_name(name)?;
+        let name_ptr = c_name.as_ptr();
+
+        let dims: Vec<i32> = dims.iter().map(|x| *x as i32).collect();
+        let dims_ptr = dims.as_ptr();
+        let dims_len = dims.len() as size_t;
+
+        #[allow(clippy::forgetting_copy_types, deprecated, clippy::transmute_num_to_bytes)]
+        let r = unsafe {
+            cpp!([
+                runner as "SignatureRunner*",
+                name_ptr as "const char*",
+                dims_ptr as "const int*",
+                dims_len as "size_t"
+            ] -> bool as "bool" {
+                std::vector<int> dims(dims_ptr, dims_ptr + dims_len);
+                return runner->ResizeInputTensor(name_ptr, dims) == kTfLiteOk;
+            })
+        };
+        if r {
+            Ok(())
+        } else {
+            Err(self.error_reporter.error(Error::Allocation, "failed to resize input tensor"))
+        }
+    }
+
+    /// Updates allocations for all tensors of this signature.
+    pub fn allocate_tensors(&mut self) -> Result<()> {
+        self.error_reporter.clear();
+        let runner = &mut *self.handle;
+
+        #[allow(clippy::forgetting_copy_types, deprecated)]
+        let status = unsafe {
+            cpp!([runner as "SignatureRunner*"] -> TfLiteStatus as "TfLiteStatus" {
+                return runner->AllocateTensors();
+            })
+        };
+        if status == TfLiteStatus::kTfLiteOk {
+            Ok(())
+        } else {
+            Err(self.error_reporter.status_error(
+                status,
+                Error::Allocation,
+                "failed to allocate tensors",
+            ))
+        }
+    }
+
+    /// Invokes the subgraph of this signature.
+    pub fn invoke(&mut self) -> Result<()> {
+        let runner = &mut *self.handle;
+
+        checked_invoke(self.error_reporter, self.cancellation, "failed to invoke signature", || {
+            #[allow(deprecated)]
+            unsafe {
+                cpp!([runner as "SignatureRunner*"] -> TfLiteStatus as "TfLiteStatus" {
+                    return runner->Invoke();
+                })
+            }
+        })
+    }
+}
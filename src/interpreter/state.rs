@@ -0,0 +1,107 @@
+use std::collections::BTreeMap;
+
+use super::TensorIndex;
+use crate::{Error, Result};
+
+const MAGIC: &[u8; 4] = b"TFLS";
+const VERSION: u32 = 1;
+
+/// The contents of the variable tensors of an interpreter, e.g. the state of a recurrent
+/// model between two chunks of a stream, see `Interpreter::save_state`.
+///
+/// `to_bytes` serializes the state so that it can be stored and restored into any
+/// interpreter of the same model.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InterpreterState {
+    pub tensors: BTreeMap<TensorIndex, Vec<u8>>,
+}
+
+impl InterpreterState {
+    /// Serializes the state. The header and tensor indices are little endian, but the
+    /// tensor contents are copied as they are in memory, i.e. native endian, so the bytes
+    /// are only portable between machines of the same endianness.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let size = self.tensors.values().map(|data| 12 + data.len()).sum::<usize>();
+        let mut bytes = Vec::with_capacity(12 + size);
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&VERSION.to_le_bytes());
+        bytes.extend_from_slice(&(self.tensors.len() as u32).to_le_bytes());
+        for (tensor_index, data) in &self.tensors {
+            bytes.extend_from_slice(&tensor_index.to_le_bytes());
+            bytes.extend_from_slice(&(data.len() as u64).to_le_bytes());
+            bytes.extend_from_slice(data);
+        }
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let mut reader = Reader { bytes };
+        if reader.take(4)? != MAGIC {
+            return Err(invalid_state("bad magic"));
+        }
+        let version = u32::from_le_bytes(reader.array()?);
+        if version != VERSION {
+            return Err(invalid_state(&format!("unsupported version {version}")));
+        }
+        let count = u32::from_le_bytes(reader.array()?);
+        let mut tensors = BTreeMap::new();
+        for _ in 0..count {
+            let tensor_index = TensorIndex::from_le_bytes(reader.array()?);
+            let len = u64::from_le_bytes(reader.array()?);
+            let len = usize::try_from(len).map_err(|_| invalid_state("tensor is too large"))?;
+            if tensors.insert(tensor_index, reader.take(len)?.to_vec()).is_some() {
+                return Err(invalid_state(&format!("duplicate tensor {tensor_index}")));
+            }
+        }
+        if !reader.bytes.is_empty() {
+            return Err(invalid_state("trailing bytes"));
+        }
+        Ok(Self { tensors })
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.bytes.len() < len {
+            return Err(invalid_state("unexpected end of data"));
+        }
+        let (head, tail) = self.bytes.split_at(len);
+        self.bytes = tail;
+        Ok(head)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        Ok(self.take(N)?.try_into().unwrap())
+    }
+}
+
+fn invalid_state(reason: &str) -> Error {
+    Error::InvalidState(reason.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bytes_round_trip() {
+        let state =
+            InterpreterState { tensors: BTreeMap::from([(3, vec![1, 2, 3, 4]), (7, Vec::new())]) };
+        let bytes = state.to_bytes();
+        assert_eq!(InterpreterState::from_bytes(&bytes).unwrap(), state);
+
+        assert!(InterpreterState::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        assert!(InterpreterState::from_bytes(&[bytes.as_slice(), &[0]].concat()).is_err());
+        assert!(matches!(InterpreterState::from_bytes(b"TFLX"), Err(Error::InvalidState(_))));
+
+        // Tensor 3 twice.
+        let mut duplicate = bytes[..12].to_vec();
+        duplicate[8] = 2;
+        duplicate.extend_from_slice(&[&bytes[12..28], &bytes[12..28]].concat());
+        assert!(matches!(InterpreterState::from_bytes(&duplicate), Err(Error::InvalidState(_))));
+    }
+}
@@ -0,0 +1,387 @@
+use std::ffi::CStr;
+use std::os::raw::c_char;
+use std::{mem, ptr, slice};
+
+use libc::{c_int, size_t};
+
+use super::cancellation::Cancellation;
+use super::context::{check_elem_kind_of, ElemKindOf, TensorInfo};
+use super::error_reporter::ErrorReporter;
+use super::node::{delegate_kernel_of, NodeInfo};
+use super::{checked_invoke, invalid_tensor_index, tensor_data_ptr, TensorIndex};
+use crate::bindings::tflite as bindings;
+use crate::bindings::{TfLiteNode, TfLiteRegistration, TfLiteStatus, TfLiteTensor};
+use crate::{Error, Result};
+
+cpp! {{
+    #include "tensorflow/lite/core/subgraph.h"
+
+    using namespace tflite;
+}}
+
+/// Memory used by the tensors, after `tflite::SubgraphAllocInfo`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MemoryInfo {
+    /// Size of the arena planned for the non-persistent tensors, reused between nodes.
+    pub arena_size: usize,
+    /// Size of the arena of the persistent tensors, e.g. variable tensors.
+    pub arena_persist_size: usize,
+    /// Size of the dynamic tensors, allocated outside the arenas.
+    pub dynamic_size: usize,
+    /// Size of the resources, e.g. of `VAR_HANDLE` operators.
+    pub resource_size: usize,
+}
+
+/// A subgraph of the model, e.g. the condition or body of a `WHILE` operator or the
+/// graph of a signature. Subgraph `0` is the primary subgraph run by `Interpreter::invoke`.
+///
+/// The subgraph is owned by the `Interpreter` it was obtained from and borrows it mutably.
+pub struct Subgraph<'i> {
+    handle: &'i mut bindings::Subgraph,
+    index: usize,
+    error_reporter: &'i ErrorReporter,
+    cancellation: Option<&'i Cancellation>,
+}
+
+impl<'i> Subgraph<'i> {
+    pub(crate) fn new(
+        handle: &'i mut bindings::Subgraph,
+        index: usize,
+        error_reporter: &'i ErrorReporter,
+        cancellation: Option<&'i Cancellation>,
+    ) -> Self {
+        Self { handle, index, error_reporter, cancellation }
+    }
+
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Returns the name of the subgraph in the model, which may be empty.
+    pub fn name(&self) -> String {
+        let subgraph = &*self.handle;
+
+        #[allow(clippy::forgetting_copy_types, deprecated)]
+        let name = unsafe {
+            CStr::from_ptr(cpp!([subgraph as "const Subgraph*"] -> *const c_char as "const char*" {
+                return subgraph->GetName().c_str();
+            }))
+        };
+        name.to_string_lossy().into_owned()
+    }
+
+    /// Read only access to list of inputs.
+    pub fn inputs(&self) -> &[TensorIndex] {
+        let subgraph = &*self.handle;
+        let mut count: size_t = 0;
+
+        #[allow(clippy::forgetting_copy_types, deprecated, clippy::transmute_num_to_bytes)]
+        let ptr = unsafe {
+            cpp!([
+                subgraph as "const Subgraph*",
+                mut count as "size_t"
+            ] -> *const TensorIndex as "const int*" {
+                const auto& inputs = subgraph->inputs();
+                count = inputs.size();
+                return inputs.data();
+            })
+        };
+        Self::indices(ptr, count)
+    }
+
+    /// Read only access to list of outputs.
+    pub fn outputs(&self) -> &[TensorIndex] {
+        let subgraph = &*self.handle;
+        let mut count: size_t = 0;
+
+        #[allow(clippy::forgetting_copy_types, deprecated, clippy::transmute_num_to_bytes)]
+        let ptr = unsafe {
+            cpp!([
+                subgraph as "const Subgraph*",
+                mut count as "size_t"
+            ] -> *const TensorIndex as "const int*" {
+                const auto& outputs = subgraph->outputs();
+                count = outputs.size();
+                return outputs.data();
+            })
+        };
+        Self::indices(ptr, count)
+    }
+
+    /// Read only access to list of variable tensors.
+    pub fn variables(&self) -> &[TensorIndex] {
+        let subgraph = &*self.handle;
+        let mut count: size_t = 0;
+
+        #[allow(clippy::forgetting_copy_types, deprecated, clippy::transmute_num_to_bytes)]
+        let ptr = unsafe {
+            cpp!([
+                subgraph as "const Subgraph*",
+                mut count as "size_t"
+            ] -> *const TensorIndex as "const int*" {
+                const auto& variables = subgraph->variables();
+                count = variables.size();
+                return variables.data();
+            })
+        };
+        Self::indices(ptr, count)
+    }
+
+    /// Returns the indices of the nodes in the order they run.
+    pub fn execution_plan(&self) -> &[c_int] {
+        let subgraph = &*self.handle;
+        let mut count: size_t = 0;
+
+        #[allow(clippy::forgetting_copy_types, deprecated, clippy::transmute_num_to_bytes)]
+        let ptr = unsafe {
+            cpp!([
+                subgraph as "const Subgraph*",
+                mut count as "size_t"
+            ] -> *const c_int as "const int*" {
+                const auto& plan = subgraph->execution_plan();
+                count = plan.size();
+                return plan.data();
+            })
+        };
+        Self::indices(ptr, count)
+    }
+
+    fn indices<'a>(ptr: *const c_int, count: size_t) -> &'a [c_int] {
+        if count == 0 {
+            return &[];
+        }
+        unsafe { slice::from_raw_parts(ptr, count) }
+    }
+
+    /// Return the number of tensors in the subgraph.
+    pub fn tensors_size(&self) -> size_t {
+        let subgraph = &*self.handle;
+
+        #[allow(clippy::forgetting_copy_types, deprecated)]
+        unsafe {
+            cpp!([subgraph as "const Subgraph*"] -> size_t as "size_t" {
+                return subgraph->tensors_size();
+            })
+        }
+    }
+
+    /// Return the number of ops in the subgraph.
+    pub fn nodes_size(&self) -> size_t {
+        let subgraph = &*self.handle;
+
+        #[allow(clippy::forgetting_copy_types, deprecated)]
+        unsafe {
+            cpp!([subgraph as "const Subgraph*"] -> size_t as "size_t" {
+                return subgraph->nodes_size();
+            })
+        }
+    }
+
+    /// Returns the memory planned and allocated for the tensors of the subgraph.
+    pub fn memory_info(&self) -> MemoryInfo {
+        let subgraph = &*self.handle;
+        let mut info = MemoryInfo::default();
+        let info_ptr = &mut info as *mut MemoryInfo;
+
+        #[allow(clippy::forgetting_copy_types, deprecated)]
+        unsafe {
+            cpp!([subgraph as "const Subgraph*", info_ptr as "SubgraphAllocInfo*"] {
+                subgraph->GetMemoryAllocInfo(info_ptr);
+            })
+        };
+        info
+    }
+
+    /// Describes the node `node_index` and the operator it runs.
+    pub fn node(&self, node_index: usize) -> Result<NodeInfo> {
+        let (node, registration) = self.node_and_registration(node_index).ok_or_else(|| {
+            Error::InvalidIndex(format!(
+                "node {node_index} does not exist in subgraph {}",
+                self.index
+            ))
+        })?;
+        let mut info = unsafe { NodeInfo::from_raw(node_index, node, registration) };
+        info.delegated_by = delegate_kernel_of(node_index, self.execution_plan(), |index| {
+            self.node_and_registration(index).map(|(node, _)| node)
+        });
+        Ok(info)
+    }
+
+    fn node_and_registration(
+        &self,
+        node_index: usize,
+    ) -> Option<(&TfLiteNode, &TfLiteRegistration)> {
+        let subgraph = &*self.handle;
+        let mut node: *const TfLiteNode = ptr::null();
+        let mut registration: *const TfLiteRegistration = ptr::null();
+
+        #[allow(clippy::forgetting_copy_types, deprecated, clippy::transmute_num_to_bytes)]
+        unsafe {
+            cpp!([
+                subgraph as "const Subgraph*",
+                node_index as "size_t",
+                mut node as "const TfLiteNode*",
+                mut registration as "const TfLiteRegistration*"
+            ] {
+                if (node_index < subgraph->nodes_size()) {
+                    const auto* node_and_registration =
+                        subgraph->node_and_registration(static_cast<int>(node_index));
+                    if (node_and_registration != nullptr) {
+                        node = &node_and_registration->first;
+                        registration = &node_and_registration->second;
+                    }
+                }
+            })
+        };
+        if node.is_null() || registration.is_null() {
+            return None;
+        }
+        Some(unsafe { (&*node, &*registration) })
+    }
+
+    /// Iterates over all nodes of the subgraph.
+    pub fn nodes(&self) -> impl Iterator<Item = NodeInfo> + '_ {
+        (0..self.nodes_size()).filter_map(|index| self.node(index).ok())
+    }
+
+    fn tensor_inner(&self, tensor_index: TensorIndex) -> Option<&TfLiteTensor> {
+        let subgraph = &*self.handle;
+
+        #[allow(clippy::forgetting_copy_types, deprecated, clippy::transmute_num_to_bytes)]
+        let ptr = unsafe {
+            cpp!([
+                subgraph as "const Subgraph*",
+                tensor_index as "int"
+            ] -> *const TfLiteTensor as "const TfLiteTensor*" {
+                if (tensor_index < 0 || static_cast<size_t>(tensor_index) >= subgraph->tensors_size()) {
+                    return nullptr;
+                }
+                return subgraph->tensor(tensor_index);
+            })
+        };
+        unsafe { ptr.as_ref() }
+    }
+
+    /// Like `tensor_inner`, but borrows the subgraph mutably, so that the data of the
+    /// tensor may be written.
+    fn tensor_inner_mut(&mut self, tensor_index: TensorIndex) -> Option<&mut TfLiteTensor> {
+        let subgraph = &mut *self.handle;
+
+        #[allow(clippy::forgetting_copy_types, deprecated, clippy::transmute_num_to_bytes)]
+        let ptr = unsafe {
+            cpp!([
+                subgraph as "Subgraph*",
+                tensor_index as "int"
+            ] -> *mut TfLiteTensor as "TfLiteTensor*" {
+                if (tensor_index < 0 || static_cast<size_t>(tensor_index) >= subgraph->tensors_size()) {
+                    return nullptr;
+                }
+                return subgraph->tensor(tensor_index);
+            })
+        };
+        unsafe { ptr.as_mut() }
+    }
+
+    pub fn tensor_info(&self, tensor_index: TensorIndex) -> Option<TensorInfo> {
+        Some(self.tensor_inner(tensor_index)?.into())
+    }
+
+    pub fn tensor_data<T>(&self, tensor_index: TensorIndex) -> Result<&[T]>
+    where
+        T: ElemKindOf,
+    {
+        let inner =
+            self.tensor_inner(tensor_index).ok_or_else(|| invalid_tensor_index(tensor_index))?;
+        check_elem_kind_of::<T>(inner.type_)?;
+        let ptr = tensor_data_ptr::<T>(inner)?;
+
+        Ok(unsafe { slice::from_raw_parts(ptr, inner.bytes / mem::size_of::<T>()) })
+    }
+
+    pub fn tensor_data_mut<T>(&mut self, tensor_index: TensorIndex) -> Result<&mut [T]>
+    where
+        T: ElemKindOf,
+    {
+        let inner = self
+            .tensor_inner_mut(tensor_index)
+            .ok_or_else(|| invalid_tensor_index(tensor_index))?;
+        check_elem_kind_of::<T>(inner.type_)?;
+        let ptr = tensor_data_ptr::<T>(inner)?;
+
+        Ok(unsafe { slice::from_raw_parts_mut(ptr, inner.bytes / mem::size_of::<T>()) })
+    }
+
+    /// Changes the dimensionality of the input `tensor_index` of the subgraph.
+    /// `allocate_tensors` must be called before the tensors are accessed again.
+    pub fn resize_input_tensor(&mut self, tensor_index: TensorIndex, dims: &[usize]) -> Result<()> {
+        if !self.inputs().contains(&tensor_index) {
+            return Err(Error::InvalidIndex(format!(
+                "tensor {tensor_index} is not an input of subgraph {}",
+                self.index
+            )));
+        }
+        self.error_reporter.clear();
+        let subgraph = &mut *self.handle;
+
+        let dims: Vec<c_int> = dims.iter().map(|x| *x as c_int).collect();
+        let dims_ptr = dims.as_ptr();
+        let dims_len = dims.len() as size_t;
+
+        #[allow(clippy::forgetting_copy_types, deprecated, clippy::transmute_num_to_bytes)]
+        let status = unsafe {
+            cpp!([
+                subgraph as "Subgraph*",
+                tensor_index as "int",
+                dims_ptr as "const int*",
+                dims_len as "size_t"
+            ] -> TfLiteStatus as "TfLiteStatus" {
+                std::vector<int> dims(dims_ptr, dims_ptr + dims_len);
+                return subgraph->ResizeInputTensor(tensor_index, dims);
+            })
+        };
+        if status == TfLiteStatus::kTfLiteOk {
+            Ok(())
+        } else {
+            Err(self.error_reporter.error(Error::Allocation, "failed to resize input tensor"))
+        }
+    }
+
+    /// Updates allocations for all tensors of the subgraph. Subgraphs other than the
+    /// primary one are allocated by the operators running them, so this must be called
+    /// before their tensors are accessed or they are invoked directly.
+    pub fn allocate_tensors(&mut self) -> Result<()> {
+        self.error_reporter.clear();
+        let subgraph = &mut *self.handle;
+
+        #[allow(clippy::forgetting_copy_types, deprecated)]
+        let status = unsafe {
+            cpp!([subgraph as "Subgraph*"] -> TfLiteStatus as "TfLiteStatus" {
+                return subgraph->AllocateTensors();
+            })
+        };
+        if status == TfLiteStatus::kTfLiteOk {
+            Ok(())
+        } else {
+            Err(self.error_reporter.status_error(
+                status,
+                Error::Allocation,
+                "failed to allocate tensors",
+            ))
+        }
+    }
+
+    /// Invokes the subgraph on its own, as if it was the primary subgraph.
+    pub fn invoke(&mut self) -> Result<()> {
+        let subgraph = &mut *self.handle;
+
+        checked_invoke(self.error_reporter, self.cancellation, "failed to invoke subgraph", || {
+            #[allow(deprecated)]
+            unsafe {
+                cpp!([subgraph as "Subgraph*"] -> TfLiteStatus as "TfLiteStatus" {
+                    return subgraph->Invoke();
+                })
+            }
+        })
+    }
+}
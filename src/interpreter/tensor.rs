@@ -0,0 +1,234 @@
+use std::ops::{Deref, DerefMut};
+use std::{mem, ptr, slice};
+
+use super::context::{check_elem_kind_of, ElemKindOf, ElementKind, TensorInfo};
+use crate::{Error, Result};
+
+/// A copy of a tensor, which outlives the interpreter it was taken from.
+#[derive(Debug, Clone)]
+pub struct OwnedTensor {
+    pub info: TensorInfo,
+    pub data: Vec<u8>,
+}
+
+impl OwnedTensor {
+    /// Copies the elements, which must be of type `T`.
+    pub fn values<T>(&self) -> Result<Vec<T>>
+    where
+        T: ElemKindOf + Copy,
+    {
+        check_elem_kind_of::<T>(self.info.element_kind)?;
+        let ptr = self.data.as_ptr() as *const T;
+        let len = self.data.len() / mem::size_of::<T>();
+
+        Ok((0..len).map(|i| unsafe { ptr::read_unaligned(ptr.add(i)) }).collect())
+    }
+
+    /// Reads a float32 tensor, or dequantizes a uint8, int8 or int16 tensor
+    /// using its quantization parameters.
+    pub fn to_f32(&self) -> Result<Vec<f32>> {
+        let info = &self.info;
+
+        match info.element_kind {
+            ElementKind::kTfLiteFloat32 => self.values::<f32>(),
+            ElementKind::kTfLiteUInt8 => info.quantization.dequantize(&info.dims, &self.data),
+            ElementKind::kTfLiteInt8 => {
+                info.quantization.dequantize(&info.dims, &self.values::<i8>()?)
+            }
+            ElementKind::kTfLiteInt16 => {
+                info.quantization.dequantize(&info.dims, &self.values::<i16>()?)
+            }
+            kind => Err(Error::TypeMismatch(format!("cannot read `{kind:?}` as f32 values"))),
+        }
+    }
+}
+
+/// The value of an input given to `Interpreter::run`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TensorValue {
+    element_kind: ElementKind,
+    dims: Vec<usize>,
+    data: Vec<u8>,
+}
+
+impl TensorValue {
+    /// Creates a value of shape `dims`, failing unless `values` fill it exactly.
+    pub fn new<T>(dims: &[usize], values: &[T]) -> Result<Self>
+    where
+        T: ElemKindOf + Copy,
+    {
+        let expected = dims.iter().product::<usize>();
+        if values.len() != expected {
+            return Err(Error::TypeMismatch(format!(
+                "expected {expected} values for a tensor of shape {dims:?}, got {}",
+                values.len()
+            )));
+        }
+        let bytes = unsafe {
+            slice::from_raw_parts(values.as_ptr() as *const u8, mem::size_of_val(values))
+        };
+        Ok(Self { element_kind: T::elem_kind_of(), dims: dims.to_vec(), data: bytes.to_vec() })
+    }
+
+    pub fn element_kind(&self) -> ElementKind {
+        self.element_kind
+    }
+
+    pub fn dims(&self) -> &[usize] {
+        &self.dims
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+impl From<OwnedTensor> for TensorValue {
+    fn from(tensor: OwnedTensor) -> Self {
+        Self { element_kind: tensor.info.element_kind, dims: tensor.info.dims, data: tensor.data }
+    }
+}
+
+/// A typed view of an input of an interpreter, see `Interpreter::input`.
+///
+/// The view borrows the interpreter mutably, so the tensors cannot be reallocated
+/// while it is alive, and using it after `invoke` does not compile:
+///
+/// ```compile_fail
+/// # use tflite::ops::builtin::BuiltinOpResolver;
+/// # use tflite::{FlatBufferModel, InterpreterBuilder, Result};
+/// # fn main() -> Result<()> {
+/// let model = FlatBufferModel::build_from_file("data/MNISTnet_uint8_quant.tflite")?;
+/// let mut interpreter = InterpreterBuilder::new(&model, BuiltinOpResolver::default())?.build()?;
+/// let mut input = interpreter.input::<u8>(0)?;
+/// interpreter.invoke()?;
+/// input.copy_from_slice(&[0; 28 * 28])?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct InputTensor<'i, T> {
+    info: TensorInfo,
+    data: &'i mut [T],
+}
+
+impl<'i, T> InputTensor<'i, T> {
+    pub(crate) fn new(info: TensorInfo, data: &'i mut [T]) -> Self {
+        Self { info, data }
+    }
+
+    #[cfg(feature = "ndarray")]
+    pub(crate) fn into_parts(self) -> (TensorInfo, &'i mut [T]) {
+        (self.info, self.data)
+    }
+
+    pub fn info(&self) -> &TensorInfo {
+        &self.info
+    }
+
+    pub fn shape(&self) -> &[usize] {
+        &self.info.dims
+    }
+
+    /// Writes `values`, failing unless they fill the tensor exactly.
+    pub fn copy_from_slice(&mut self, values: &[T]) -> Result<()>
+    where
+        T: Copy,
+    {
+        check_len(&self.info, values.len())?;
+        self.data.copy_from_slice(values);
+        Ok(())
+    }
+}
+
+impl<T> Deref for InputTensor<'_, T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        self.data
+    }
+}
+
+impl<T> DerefMut for InputTensor<'_, T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        self.data
+    }
+}
+
+/// A typed view of an output of an interpreter, see `Interpreter::output`.
+///
+/// The view borrows the interpreter, so it must be dropped before the next `invoke`.
+pub struct OutputTensor<'i, T> {
+    info: TensorInfo,
+    data: &'i [T],
+}
+
+impl<'i, T> OutputTensor<'i, T> {
+    pub(crate) fn new(info: TensorInfo, data: &'i [T]) -> Self {
+        Self { info, data }
+    }
+
+    pub fn info(&self) -> &TensorInfo {
+        &self.info
+    }
+
+    pub fn shape(&self) -> &[usize] {
+        &self.info.dims
+    }
+
+    pub fn as_slice(&self) -> &'i [T] {
+        self.data
+    }
+
+    /// Copies the values into `values`, failing unless it has the size of the tensor.
+    pub fn copy_to_slice(&self, values: &mut [T]) -> Result<()>
+    where
+        T: Copy,
+    {
+        check_len(&self.info, values.len())?;
+        values.copy_from_slice(self.data);
+        Ok(())
+    }
+}
+
+impl<T> Deref for OutputTensor<'_, T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        self.data
+    }
+}
+
+/// Fails unless `len` values fill the tensor exactly.
+pub(crate) fn check_len(info: &TensorInfo, len: usize) -> Result<()> {
+    let expected = info.dims.iter().product::<usize>();
+    if len != expected {
+        return Err(Error::TypeMismatch(format!(
+            "expected {expected} values for a tensor of shape {:?}, got {len}",
+            info.dims
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::context::Quantization;
+
+    #[test]
+    fn values() {
+        let tensor = OwnedTensor {
+            info: TensorInfo {
+                name: "t".to_string(),
+                element_kind: ElementKind::kTfLiteInt16,
+                dims: vec![3],
+                quantization: Quantization::PerTensor { scale: 0.5, zero_point: 1 },
+            },
+            data: [1i16, 3, -1].iter().flat_map(|x| x.to_ne_bytes()).collect(),
+        };
+        assert_eq!(tensor.values::<i16>().unwrap(), [1, 3, -1]);
+        assert!(tensor.values::<u16>().is_err());
+        assert_eq!(tensor.to_f32().unwrap(), [0.0, 1.0, -1.0]);
+    }
+}
@@ -116,18 +116,19 @@ pub struct MetadataT {
 #[repr(C)]
 #[derive(Debug)]
 pub struct TensorMapT {
-    name: StlString,
-    tensor_index: u32,
+    _vtable: NativeTable,
+    pub name: StlString,
+    pub tensor_index: u32,
 }
 
 #[repr(C)]
 #[derive(Debug)]
 pub struct SignatureDefT {
-    inputs: VectorOfUniquePtr<TensorMapT>,
-    outputs: VectorOfUniquePtr<TensorMapT>,
-    signature_key: StlString,
-    deprecated_tag: StlString,
-    subgraph_index: u32,
+    _vtable: NativeTable,
+    pub inputs: VectorOfUniquePtr<TensorMapT>,
+    pub outputs: VectorOfUniquePtr<TensorMapT>,
+    pub signature_key: StlString,
+    pub subgraph_index: u32,
 }
 
 #[repr(C)]
@@ -293,7 +294,7 @@ impl Model {
 
     pub fn from_file<P: AsRef<Path>>(filepath: P) -> Result<Self> {
         Self::from_buffer(&fs::read(filepath)?)
-            .ok_or_else(|| Error::internal_error("failed to unpack the flatbuffer model"))
+            .ok_or_else(|| Error::ModelVerification("failed to unpack the flatbuffer model".into()))
     }
 
     pub fn to_buffer(&self) -> Vec<u8> {
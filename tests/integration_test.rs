@@ -1,8 +1,31 @@
 use std::fs::{self, File};
 use std::io::Read;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
 
+use tflite::context::{ElementKind, Quantization, TensorView, TensorViewMut};
+use tflite::delegate::{
+    CustomDelegate, Delegate, DelegateKernel, Partition, XnnpackDelegate, XnnpackOptions,
+};
+use tflite::model::BuiltinOperator;
+use tflite::op_resolver::OpResolver;
 use tflite::ops::builtin::BuiltinOpResolver;
-use tflite::{FlatBufferModel, InterpreterBuilder, Result};
+use tflite::profiler::RecordingProfiler;
+use tflite::{
+    DynInterpreter, FlatBufferModel, Interpreter, InterpreterBuilder, InterpreterOptions,
+    InterpreterPool, InterpreterPoolOptions, NodeInfo, Result, TensorValue,
+};
+
+const MNIST_MODEL: &str = "data/MNISTnet_uint8_quant.tflite";
+
+/// Builds an interpreter of the quantized MNIST model with `resolver`.
+fn mnist_interpreter(
+    resolver: BuiltinOpResolver,
+) -> Result<Interpreter<'static, BuiltinOpResolver>> {
+    let model = FlatBufferModel::build_from_file(MNIST_MODEL)?;
+    InterpreterBuilder::new(model, resolver)?.build()
+}
 
 fn test_mnist(model: &FlatBufferModel) -> Result<()> {
     let resolver = BuiltinOpResolver::default();
@@ -45,12 +68,34 @@ fn test_mnist(model: &FlatBufferModel) -> Result<()> {
 
 #[test]
 fn mobilenetv1_mnist() -> Result<()> {
-    test_mnist(&FlatBufferModel::build_from_file("data/MNISTnet_uint8_quant.tflite")?)?;
+    test_mnist(&FlatBufferModel::build_from_file(MNIST_MODEL)?)?;
 
-    let buf = fs::read("data/MNISTnet_uint8_quant.tflite")?;
+    let buf = fs::read(MNIST_MODEL)?;
     test_mnist(&FlatBufferModel::build_from_buffer(buf)?)
 }
 
+#[test]
+fn mnist_mmap_and_static_models() -> Result<()> {
+    #[repr(C, align(16))]
+    struct Aligned<T: ?Sized>(T);
+
+    static MODEL: &Aligned<[u8]> = &Aligned(*include_bytes!("../data/MNISTnet_uint8_quant.tflite"));
+
+    let buf = fs::read(MNIST_MODEL)?;
+
+    let model = FlatBufferModel::build_from_mmap(MNIST_MODEL)?;
+    assert_eq!(model.buffer(), &buf[..]);
+    test_mnist(&model)?;
+
+    let model = FlatBufferModel::build_from_static(&MODEL.0)?;
+    assert_eq!(model.buffer().as_ptr(), MODEL.0.as_ptr());
+    test_mnist(&model)?;
+    assert!(FlatBufferModel::build_from_static(&MODEL.0[1..]).is_err());
+
+    assert!(FlatBufferModel::build_from_mmap("data/missing.tflite").is_err());
+    Ok(())
+}
+
 #[test]
 fn mobilenetv2_mnist() -> Result<()> {
     test_mnist(&FlatBufferModel::build_from_file("data/MNISTnet_v2_uint8_quant.tflite")?)?;
@@ -58,3 +103,418 @@ fn mobilenetv2_mnist() -> Result<()> {
     let buf = fs::read("data/MNISTnet_v2_uint8_quant.tflite")?;
     test_mnist(&FlatBufferModel::build_from_buffer(buf)?)
 }
+
+#[test]
+fn mnist_quantized_f32_helpers() -> Result<()> {
+    let mut interpreter = mnist_interpreter(BuiltinOpResolver::default())?;
+
+    let input_index = interpreter.inputs()[0];
+    let output_index = interpreter.outputs()[0];
+
+    let input_tensor = interpreter.tensor_info(input_index).unwrap();
+    assert!(matches!(input_tensor.quantization, Quantization::PerTensor { .. }));
+    let output_tensor = interpreter.tensor_info(output_index).unwrap();
+    assert!(output_tensor.quantization.is_quantized());
+
+    let mut image = vec![0u8; 28 * 28];
+    File::open("data/mnist10.bin")?.read_exact(&mut image)?;
+
+    let values = input_tensor.quantization.dequantize(&input_tensor.dims, &image)?;
+    interpreter.set_input_f32(input_index, &values)?;
+    assert_eq!(interpreter.tensor_data::<u8>(input_index)?, &image[..]);
+
+    interpreter.invoke()?;
+
+    let output = interpreter.output_as_f32(output_index)?;
+    assert_eq!(output.len(), 10);
+    let guess = output.iter().enumerate().max_by(|x, y| x.1.total_cmp(y.1)).unwrap().0;
+    assert_eq!(guess, 0);
+    assert!((output.iter().sum::<f32>() - 1.0).abs() < 0.05);
+    Ok(())
+}
+
+#[test]
+fn mnist_typed_tensors() -> Result<()> {
+    let mut interpreter = mnist_interpreter(BuiltinOpResolver::default())?;
+
+    let mut images = vec![0u8; 10 * 28 * 28];
+    File::open("data/mnist10.bin")?.read_exact(&mut images)?;
+
+    let mut input = interpreter.input::<u8>(0)?;
+    assert_eq!(input.shape(), [1, 28, 28, 1]);
+    assert!(input.copy_from_slice(&images).is_err());
+    input.copy_from_slice(&images[..28 * 28])?;
+    assert!(interpreter.input::<f32>(0).is_err());
+    assert!(interpreter.input::<u8>(1).is_err());
+
+    interpreter.invoke()?;
+
+    let output = interpreter.output::<u8>(0)?;
+    assert_eq!(output.shape(), [1, 10]);
+    let guess = output.iter().enumerate().max_by(|x, y| x.1.cmp(y.1)).unwrap().0;
+    assert_eq!(guess, 0);
+    let mut scores = [0u8; 10];
+    output.copy_to_slice(&mut scores)?;
+    assert_eq!(scores, output.as_slice());
+    Ok(())
+}
+
+#[test]
+fn mnist_run_by_name() -> Result<()> {
+    let mut interpreter = mnist_interpreter(BuiltinOpResolver::default())?;
+    let input = interpreter.get_input_details()?.remove(0);
+    let output = interpreter.get_output_details()?.remove(0);
+
+    let mut input_file = File::open("data/mnist10.bin")?;
+    for i in 0..10 {
+        let mut image = vec![0u8; 28 * 28];
+        input_file.read_exact(&mut image)?;
+        let value = TensorValue::new(&input.dims, &image)?;
+        let outputs = interpreter.run(&[(input.name.as_str(), value)])?;
+
+        let scores = outputs[&output.name].values::<u8>()?;
+        assert_eq!(scores, interpreter.output_by_name::<u8>(&output.name)?.as_slice());
+        assert_eq!(scores.iter().enumerate().max_by_key(|x| x.1).unwrap().0, i);
+    }
+    Ok(())
+}
+
+#[cfg(feature = "ndarray")]
+#[test]
+fn mnist_ndarray() -> Result<()> {
+    let mut interpreter = mnist_interpreter(BuiltinOpResolver::default())?;
+
+    let mut image = vec![0u8; 28 * 28];
+    File::open("data/mnist10.bin")?.read_exact(&mut image)?;
+
+    let mut input = interpreter.input_array_mut::<u8>(0)?;
+    assert_eq!(input.shape(), [1, 28, 28, 1]);
+    input.assign(&ndarray::ArrayView4::from_shape((1, 28, 28, 1), &image[..]).unwrap());
+    interpreter.invoke()?;
+
+    let output = interpreter.output_array::<u8>(0)?;
+    assert_eq!(output.shape(), [1, 10]);
+    let scores = output.index_axis(ndarray::Axis(0), 0);
+    assert_eq!(scores.iter().enumerate().max_by_key(|x| x.1).unwrap().0, 0);
+    Ok(())
+}
+
+#[cfg(feature = "image")]
+#[test]
+fn mnist_image_preprocessing() -> Result<()> {
+    use tflite::preprocessing::{ImageOptions, Normalization};
+
+    let mut interpreter = mnist_interpreter(BuiltinOpResolver::default())?;
+    let input_index = interpreter.inputs()[0];
+
+    // Normalize to the real values the quantized input represents.
+    let Quantization::PerTensor { scale, zero_point } =
+        interpreter.tensor_info(input_index).unwrap().quantization
+    else {
+        panic!("input is not quantized per tensor");
+    };
+    let options = ImageOptions {
+        normalization: Normalization::MeanStd {
+            mean: [zero_point as f32; 3],
+            std: [1.0 / scale; 3],
+        },
+        ..Default::default()
+    };
+
+    let mut input_file = File::open("data/mnist10.bin")?;
+    for i in 0..10 {
+        let mut pixels = vec![0u8; 28 * 28];
+        input_file.read_exact(&mut pixels)?;
+        let image = image::GrayImage::from_raw(28, 28, pixels).unwrap();
+        // Upscaled images are resized back to the 28x28 input.
+        let image = image::DynamicImage::ImageLuma8(image).resize_exact(
+            56,
+            56,
+            image::imageops::FilterType::Nearest,
+        );
+
+        interpreter.set_input_image(input_index, &image, &options)?;
+        interpreter.invoke()?;
+        let output = interpreter.output::<u8>(0)?;
+        assert_eq!(output.iter().enumerate().max_by_key(|x| x.1).unwrap().0, i);
+    }
+    Ok(())
+}
+
+fn mnist_guesses<Op: OpResolver>(interpreter: &mut Interpreter<'_, Op>) -> Result<Vec<usize>> {
+    let input_index = interpreter.inputs()[0];
+    let output_index = interpreter.outputs()[0];
+
+    let mut input_file = File::open("data/mnist10.bin")?;
+    let mut guesses = Vec::new();
+    for _ in 0..10 {
+        input_file.read_exact(interpreter.tensor_data_mut(input_index)?)?;
+        interpreter.invoke()?;
+        let output: &[u8] = interpreter.tensor_data(output_index)?;
+        guesses.push(output.iter().enumerate().max_by(|x, y| x.1.cmp(y.1)).unwrap().0);
+    }
+    Ok(guesses)
+}
+
+#[test]
+fn mnist_xnnpack_delegate() -> Result<()> {
+    let model = FlatBufferModel::build_from_file(MNIST_MODEL)?;
+    let resolver = BuiltinOpResolver::without_default_delegates();
+    let expected: Vec<usize> = (0..10).collect();
+
+    let mut interpreter = InterpreterBuilder::new(&model, &resolver)?.build()?;
+    assert_eq!(mnist_guesses(&mut interpreter)?, expected);
+
+    let options = XnnpackOptions::default().num_threads(2);
+    let mut interpreter = InterpreterBuilder::new(&model, &resolver)?
+        .add_delegate(XnnpackDelegate::new(&options)?)
+        .build()?;
+    assert_eq!(mnist_guesses(&mut interpreter)?, expected);
+
+    let mut interpreter = InterpreterBuilder::new(&model, &resolver)?.build()?;
+    interpreter.modify_graph_with_delegate(XnnpackDelegate::new(&XnnpackOptions::default())?)?;
+    assert_eq!(mnist_guesses(&mut interpreter)?, expected);
+    Ok(())
+}
+
+/// Runs the quantized softmax nodes of a graph in Rust.
+struct SoftmaxDelegate {
+    invocations: Arc<AtomicUsize>,
+}
+
+struct SoftmaxKernel {
+    invocations: Arc<AtomicUsize>,
+}
+
+impl Delegate for SoftmaxDelegate {
+    fn supports_node(&self, node: &NodeInfo, tensors: &[TensorView<'_>]) -> bool {
+        node.builtin_code == BuiltinOperator::BuiltinOperator_SOFTMAX as i32
+            && tensors[node.inputs[0] as usize].info().element_kind == ElementKind::kTfLiteUInt8
+    }
+
+    fn create_kernel(
+        &self,
+        partition: &Partition,
+        _tensors: &[TensorView<'_>],
+    ) -> Result<Box<dyn DelegateKernel>> {
+        assert_eq!(partition.nodes.len(), 1);
+        assert_eq!(partition.inputs.len(), 1);
+        assert_eq!(partition.outputs.len(), 1);
+        Ok(Box::new(SoftmaxKernel { invocations: self.invocations.clone() }))
+    }
+}
+
+impl DelegateKernel for SoftmaxKernel {
+    fn invoke(
+        &mut self,
+        inputs: &[TensorView<'_>],
+        outputs: &mut [TensorViewMut<'_>],
+    ) -> Result<()> {
+        let input = inputs[0].info();
+        let logits = input.quantization.dequantize(&input.dims, inputs[0].data::<u8>()?)?;
+        let max = logits.iter().copied().fold(f32::MIN, f32::max);
+        let exps: Vec<f32> = logits.iter().map(|x| (x - max).exp()).collect();
+        let sum: f32 = exps.iter().sum();
+        let probabilities: Vec<f32> = exps.iter().map(|x| x / sum).collect();
+
+        let output = outputs[0].info();
+        let quantized = output.quantization.quantize::<u8>(&output.dims, &probabilities)?;
+        outputs[0].data_mut::<u8>()?.copy_from_slice(&quantized);
+        self.invocations.fetch_add(1, Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+#[test]
+fn mnist_custom_delegate() -> Result<()> {
+    let mut interpreter = mnist_interpreter(BuiltinOpResolver::without_default_delegates())?;
+    let nodes_size = interpreter.nodes_size();
+
+    let invocations = Arc::new(AtomicUsize::new(0));
+    let delegate = SoftmaxDelegate { invocations: invocations.clone() };
+    interpreter.modify_graph_with_delegate(CustomDelegate::new(delegate))?;
+    assert_eq!(interpreter.nodes_size(), nodes_size + 1);
+
+    let plan: Vec<NodeInfo> = interpreter.execution_plan_nodes().collect();
+    assert_eq!(plan.len(), nodes_size);
+    let last = plan.last().unwrap();
+    assert!(last.delegated);
+    assert_eq!(last.op_name, "CustomDelegate");
+    assert!(plan.iter().all(|node| node.delegated == (node.index == nodes_size)));
+    assert!(plan.iter().all(|node| node.delegated_by.is_none()));
+
+    let replaced: Vec<NodeInfo> =
+        interpreter.nodes().filter(|node| node.delegated_by.is_some()).collect();
+    assert_eq!(replaced.len(), 1);
+    assert_eq!(replaced[0].op_name, "SOFTMAX");
+    assert_eq!(replaced[0].delegated_by, Some(nodes_size));
+
+    assert_eq!(mnist_guesses(&mut interpreter)?, (0..10).collect::<Vec<_>>());
+    assert_eq!(invocations.load(Ordering::SeqCst), 10);
+    Ok(())
+}
+
+#[test]
+fn mnist_profiler_with_delegate() -> Result<()> {
+    let mut interpreter = mnist_interpreter(BuiltinOpResolver::without_default_delegates())?;
+    let delegate = SoftmaxDelegate { invocations: Arc::new(AtomicUsize::new(0)) };
+    interpreter.modify_graph_with_delegate(CustomDelegate::new(delegate))?;
+
+    let profiler = RecordingProfiler::new();
+    interpreter.set_profiler(profiler.clone());
+    interpreter.invoke()?;
+
+    // The replaced softmax node does not run, the kernel of the delegate runs instead.
+    let report = profiler.report();
+    let plan: Vec<NodeInfo> = interpreter.execution_plan_nodes().collect();
+    let profiled: Vec<usize> = report.ops.iter().map(|op| op.node_index).collect();
+    assert_eq!(profiled, plan.iter().map(|node| node.index).collect::<Vec<_>>());
+    let kernel = report.ops.last().unwrap();
+    assert_eq!(kernel.name, "CustomDelegate");
+    assert_eq!(kernel.count, 1);
+    assert!(report.ops.iter().all(|op| op.name != "SOFTMAX" && !op.delegated));
+    Ok(())
+}
+
+#[test]
+fn mnist_graph_introspection() -> Result<()> {
+    let interpreter = mnist_interpreter(BuiltinOpResolver::without_default_delegates())?;
+
+    let plan: Vec<usize> = interpreter.execution_plan().iter().map(|&i| i as usize).collect();
+    assert_eq!(plan, (0..interpreter.nodes_size()).collect::<Vec<_>>());
+
+    let nodes: Vec<NodeInfo> = interpreter.nodes().collect();
+    assert_eq!(nodes.len(), interpreter.nodes_size());
+    assert!(nodes
+        .iter()
+        .all(|node| !node.delegated && node.delegated_by.is_none() && node.custom_name.is_none()));
+    assert!(nodes.iter().any(|node| node.op_name == "CONV_2D"));
+
+    let last = interpreter.node(plan[plan.len() - 1])?;
+    assert_eq!(last.op_name, "SOFTMAX");
+    assert_eq!(last.builtin_code, BuiltinOperator::BuiltinOperator_SOFTMAX as i32);
+    assert_eq!(last.outputs, interpreter.outputs());
+    assert!(last.version >= 1);
+
+    assert!(interpreter.node(interpreter.nodes_size()).is_err());
+    Ok(())
+}
+
+#[test]
+fn mnist_preserve_all_tensors() -> Result<()> {
+    let model = FlatBufferModel::build_from_file(MNIST_MODEL)?;
+    let resolver = BuiltinOpResolver::without_default_delegates();
+    let options = InterpreterOptions::default().preserve_all_tensors(true);
+    let mut interpreter =
+        InterpreterBuilder::new(&model, &resolver)?.with_options(options).build()?;
+
+    let input_index = interpreter.inputs()[0];
+    let output_index = interpreter.outputs()[0];
+    File::open("data/mnist10.bin")?.read_exact(interpreter.tensor_data_mut(input_index)?)?;
+    interpreter.invoke()?;
+
+    let tensors = interpreter.snapshot_tensors();
+    for node in interpreter.execution_plan_nodes() {
+        for output in &node.outputs {
+            let tensor = &tensors[output];
+            let len: usize = tensor.info.dims.iter().product();
+            assert_eq!(tensor.data.len(), len, "output of {}", node.op_name);
+            assert!(tensor.data.iter().any(|&x| x != 0), "output of {}", node.op_name);
+        }
+    }
+
+    let output = &tensors[&output_index];
+    assert_eq!(output.data, interpreter.tensor_data::<u8>(output_index)?);
+    assert_eq!(output.to_f32()?, interpreter.output_as_f32(output_index)?);
+    assert!(output.values::<i8>().is_err());
+    Ok(())
+}
+
+#[test]
+fn mnist_runtime_options() -> Result<()> {
+    let model = FlatBufferModel::build_from_file(MNIST_MODEL)?;
+    let resolver = BuiltinOpResolver::without_default_delegates();
+    let mut interpreter = InterpreterBuilder::new(&model, &resolver)?.build()?;
+    let planned = interpreter.memory_info();
+    assert!(planned.arena_size > 0);
+
+    let options = InterpreterOptions::default()
+        .ensure_dynamic_tensors_are_released(true)
+        .dynamic_allocation_for_large_tensors(Some(1024));
+    let mut large_dynamic =
+        InterpreterBuilder::new(&model, &resolver)?.with_options(options).build()?;
+    // The large intermediate tensors leave the arena.
+    assert!(large_dynamic.memory_info().arena_size < planned.arena_size);
+
+    for interpreter in [&mut interpreter, &mut large_dynamic] {
+        interpreter.set_allow_fp16_precision_for_fp32(true);
+        interpreter.set_allow_buffer_handle_output(false);
+        interpreter.ensure_dynamic_tensors_are_released();
+        assert_eq!(mnist_guesses(interpreter)?, (0..10).collect::<Vec<_>>());
+    }
+    Ok(())
+}
+
+#[test]
+fn mnist_profiler() -> Result<()> {
+    let mut interpreter = mnist_interpreter(BuiltinOpResolver::without_default_delegates())?;
+
+    let profiler = RecordingProfiler::new();
+    interpreter.set_profiler(profiler.clone());
+    interpreter.invoke()?;
+    interpreter.invoke()?;
+
+    let report = profiler.report();
+    assert_eq!(report.ops.len(), interpreter.nodes_size());
+    // A header, a line per node and the total.
+    assert_eq!(report.to_string().lines().count(), report.ops.len() + 2);
+    assert!(report.ops.iter().all(|op| op.count == 2 && !op.delegated));
+    assert!(report.ops.iter().any(|op| op.name == "CONV_2D"));
+
+    interpreter.remove_profiler();
+    interpreter.invoke()?;
+    assert_eq!(profiler.report().ops[0].count, 2);
+    Ok(())
+}
+
+#[test]
+fn mnist_interpreter_pool() -> Result<()> {
+    let model = FlatBufferModel::build_from_file(MNIST_MODEL)?;
+    let resolver = BuiltinOpResolver::default();
+    let options = InterpreterPoolOptions { size: 2, ..InterpreterPoolOptions::default() };
+    let pool = InterpreterPool::new(&model, &resolver, options)?;
+    let expected: Vec<usize> = (0..10).collect();
+
+    thread::scope(|scope| {
+        let workers: Vec<_> = (0..4)
+            .map(|_| {
+                scope.spawn(|| -> Result<Vec<usize>> {
+                    let mut interpreter = pool.acquire()?;
+                    mnist_guesses(&mut interpreter)
+                })
+            })
+            .collect();
+        for worker in workers {
+            assert_eq!(worker.join().unwrap()?, expected);
+        }
+        Ok(())
+    })
+}
+
+#[test]
+fn mnist_dyn_interpreter() -> Result<()> {
+    let model = Arc::new(FlatBufferModel::build_from_file(MNIST_MODEL)?);
+    let interpreters = (0..2)
+        .map(|_| InterpreterBuilder::new_dyn(model.clone(), BuiltinOpResolver::default())?.build())
+        .collect::<Result<Vec<DynInterpreter>>>()?;
+    drop(model);
+
+    let workers: Vec<_> = interpreters
+        .into_iter()
+        .map(|mut interpreter| thread::spawn(move || mnist_guesses(&mut interpreter)))
+        .collect();
+    for worker in workers {
+        assert_eq!(worker.join().unwrap()?, (0..10).collect::<Vec<_>>());
+    }
+    Ok(())
+}